@@ -0,0 +1,269 @@
+// json-rpc batches
+// sends many calls of one method over a single client and hands back one result per input
+
+use async_trait::async_trait;
+use jsonrpsee::core::{
+    client::{ClientT, Error as ClientError},
+    params::{ArrayParams, BatchRequestBuilder},
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{
+    error::{AoriError, AoriResult},
+    AoriCancelOrderParams, AoriMakeOrderParams, OrderView, ViewOrderbookQuery,
+};
+
+/// Calls per batch, larger inputs are split over several batches
+pub const MAX_BATCH_SIZE: usize = 100;
+
+/// Calls `method` once per item of `params`. The outer error is for params that can't be
+/// encoded, otherwise the results are in the order of `params` and each call that failed is
+/// mapped through `map_err` (`AoriError::from_backend` or `AoriError::from_provider`). When a
+/// whole batch fails to send, each of its items gets the transport error and the results of
+/// earlier batches are kept
+pub async fn batch_request<C, P, R>(
+    client: &C,
+    method: &str,
+    params: Vec<P>,
+    map_err: fn(ClientError) -> AoriError,
+) -> AoriResult<Vec<AoriResult<R>>>
+where
+    C: ClientT + Sync,
+    P: Serialize,
+    R: DeserializeOwned + std::fmt::Debug,
+{
+    let mut results = Vec::with_capacity(params.len());
+    for chunk in params.chunks(MAX_BATCH_SIZE) {
+        let mut batch = BatchRequestBuilder::new();
+        for item in chunk {
+            let mut item_params = ArrayParams::new();
+            item_params.insert(item)?;
+            batch.insert(method, item_params)?;
+        }
+
+        // jsonrpsee matches the responses to their requests by id, so they come back in order
+        match client.batch_request::<R>(batch).await {
+            Ok(responses) => {
+                results.extend(responses.into_iter().map(|response| {
+                    response.map_err(|e| map_err(ClientError::Call(e.into_owned())))
+                }))
+            }
+            Err(e) => {
+                let error = map_err(e);
+                results.extend(chunk.iter().map(|_| Err(transport_error(&error))));
+            }
+        }
+    }
+    Ok(results)
+}
+
+/// Copy of a batch's transport error for each of its items, as `AoriError` isn't `Clone`
+fn transport_error(error: &AoriError) -> AoriError {
+    match error {
+        AoriError::Timeout(message) => AoriError::Timeout(message.clone()),
+        AoriError::RateLimited(message) => AoriError::RateLimited(message.clone()),
+        AoriError::Transport(message) => AoriError::Transport(message.clone()),
+        error => AoriError::Transport(error.to_string()),
+    }
+}
+
+/// Batched versions of the `AoriBackendRpcClient` calls
+#[async_trait]
+pub trait AoriBackendBatchClient {
+    async fn make_orders(
+        &self,
+        orders: Vec<AoriMakeOrderParams>,
+    ) -> AoriResult<Vec<AoriResult<OrderView>>>;
+
+    async fn cancel_orders(
+        &self,
+        orders: Vec<AoriCancelOrderParams>,
+    ) -> AoriResult<Vec<AoriResult<String>>>;
+
+    async fn view_orderbooks(
+        &self,
+        queries: Vec<ViewOrderbookQuery>,
+    ) -> AoriResult<Vec<AoriResult<Vec<OrderView>>>>;
+}
+
+#[async_trait]
+impl<C: ClientT + Sync> AoriBackendBatchClient for C {
+    async fn make_orders(
+        &self,
+        orders: Vec<AoriMakeOrderParams>,
+    ) -> AoriResult<Vec<AoriResult<OrderView>>> {
+        batch_request(self, "aori_makeOrder", orders, AoriError::from_backend).await
+    }
+
+    async fn cancel_orders(
+        &self,
+        orders: Vec<AoriCancelOrderParams>,
+    ) -> AoriResult<Vec<AoriResult<String>>> {
+        batch_request(self, "aori_cancelOrder", orders, AoriError::from_backend).await
+    }
+
+    async fn view_orderbooks(
+        &self,
+        queries: Vec<ViewOrderbookQuery>,
+    ) -> AoriResult<Vec<AoriResult<Vec<OrderView>>>> {
+        batch_request(self, "aori_viewOrderbook", queries, AoriError::from_backend).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AoriBackendErrors, AoriHasOrderSettledParams, ProviderClient};
+    use jsonrpsee::{
+        http_client::HttpClientBuilder,
+        server::{RpcModule, ServerHandle},
+        types::ErrorObject,
+    };
+    use serde_json::{json, Value};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    /// Settles every order except 0x02, and refuses cancellations of 0x02
+    async fn mock_server() -> (String, ServerHandle) {
+        let mut module = RpcModule::new(());
+        module
+            .register_method("aori_hasOrderSettled", |params, _| {
+                let (params,): (AoriHasOrderSettledParams,) = params.parse()?;
+                if params.order_hash == "0x02" {
+                    return Err(ErrorObject::owned(-32000, "Order not found", None::<()>));
+                }
+                Ok(serde_json::json!({ "id": 1, "result": params.order_hash }))
+            })
+            .unwrap();
+        module
+            .register_method("aori_cancelOrder", |params, _| {
+                let (params,): (AoriCancelOrderParams,) = params.parse()?;
+                if params.order_hash == "0x02" {
+                    return Err(ErrorObject::owned(-32000, "Invalid API Key", None::<()>));
+                }
+                Ok(params.order_hash)
+            })
+            .unwrap();
+
+        crate::test_utils::mock_server(module).await
+    }
+
+    /// Answers the first batch it receives by echoing each order hash, then shuts down
+    async fn dying_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0; 4096];
+            let body = loop {
+                let read = socket.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..read]);
+                let request = String::from_utf8_lossy(&request);
+                let Some((head, body)) = request.split_once("\r\n\r\n") else { continue };
+                let length: usize = head
+                    .lines()
+                    .find_map(|line| {
+                        line.to_lowercase().strip_prefix("content-length:")?.trim().parse().ok()
+                    })
+                    .unwrap();
+                if body.len() >= length {
+                    break body.to_string();
+                }
+            };
+
+            let calls: Vec<Value> = serde_json::from_str(&body).unwrap();
+            let responses: Vec<Value> = calls
+                .iter()
+                .map(|call| {
+                    let order_hash = &call["params"][0]["orderHash"];
+                    json!({ "jsonrpc": "2.0", "id": call["id"], "result": order_hash })
+                })
+                .collect();
+            let body = serde_json::to_string(&responses).unwrap();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\
+                 connection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        url
+    }
+
+    fn order_hash(i: usize) -> String {
+        format!("0x{:02x}", i)
+    }
+
+    #[tokio::test]
+    async fn provider_batches_report_failures_per_item() {
+        let (url, handle) = mock_server().await;
+        let provider = ProviderClient::new(vec![url]);
+
+        // spans several batches
+        let orders: Vec<AoriHasOrderSettledParams> = (0..MAX_BATCH_SIZE + 5)
+            .map(|i| AoriHasOrderSettledParams {
+                order_hash: order_hash(i),
+                zone: "0x00".into(),
+                chain_id: 1,
+            })
+            .collect();
+        let results = provider.has_orders_settled(orders).await.unwrap();
+
+        assert_eq!(results.len(), MAX_BATCH_SIZE + 5);
+        for (i, result) in results.iter().enumerate() {
+            match result {
+                Err(AoriError::Provider(message)) if i == 2 => {
+                    assert_eq!(message, "Order not found")
+                }
+                Ok(response) => assert_eq!(response.result, order_hash(i)),
+                result => panic!("unexpected result {:?} for order {}", result, i),
+            }
+        }
+
+        handle.stop().unwrap();
+    }
+
+    #[tokio::test]
+    async fn backend_batches_map_backend_errors() {
+        let (url, handle) = mock_server().await;
+        let client = HttpClientBuilder::default().build(&url).unwrap();
+
+        let orders = (1..4)
+            .map(|i| AoriCancelOrderParams { order_hash: order_hash(i), api_key: "key".into() })
+            .collect();
+        let results = client.cancel_orders(orders).await.unwrap();
+
+        assert_eq!(results[0].as_ref().unwrap(), "0x01");
+        assert!(matches!(results[1], Err(AoriError::Backend(AoriBackendErrors::InvalidAPIKey()))));
+        assert_eq!(results[2].as_ref().unwrap(), "0x03");
+        assert!(client.cancel_orders(vec![]).await.unwrap().is_empty());
+
+        handle.stop().unwrap();
+    }
+
+    #[tokio::test]
+    async fn keeps_earlier_batches_when_the_server_dies() {
+        let url = dying_server().await;
+        let client = HttpClientBuilder::default().build(&url).unwrap();
+
+        let orders = (0..MAX_BATCH_SIZE + 5)
+            .map(|i| AoriCancelOrderParams { order_hash: order_hash(i), api_key: "key".into() })
+            .collect();
+        let results = client.cancel_orders(orders).await.unwrap();
+
+        assert_eq!(results.len(), MAX_BATCH_SIZE + 5);
+        for (i, result) in results.iter().enumerate() {
+            match result {
+                Ok(hash) if i < MAX_BATCH_SIZE => assert_eq!(*hash, order_hash(i)),
+                Err(AoriError::Transport(_)) if i >= MAX_BATCH_SIZE => {}
+                result => panic!("unexpected result {:?} for order {}", result, i),
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+// command line parsing
+// `aori <command> [positional..] [--flag value] [--switch]`
+
+use std::{collections::HashMap, str::FromStr};
+
+use aori_rs::{AoriError, AoriResult};
+
+/// Flags that never take a value
+const SWITCHES: &[&str] = &["all", "help", "public"];
+
+#[derive(Debug, Default)]
+pub struct Args {
+    positional: Vec<String>,
+    flags: HashMap<String, String>,
+    switches: Vec<String>,
+}
+
+impl Args {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Self {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter().peekable();
+
+        while let Some(arg) = args.next() {
+            let Some(name) = arg.strip_prefix("--") else {
+                parsed.positional.push(arg);
+                continue;
+            };
+
+            if let Some((name, value)) = name.split_once('=') {
+                parsed.flags.insert(name.to_string(), value.to_string());
+            } else if SWITCHES.contains(&name) {
+                parsed.switches.push(name.to_string());
+            } else if let Some(value) = args.next_if(|next| !next.starts_with("--")) {
+                parsed.flags.insert(name.to_string(), value);
+            } else {
+                parsed.switches.push(name.to_string());
+            }
+        }
+
+        parsed
+    }
+
+    pub fn command(&self) -> Option<&str> {
+        self.positional.first().map(String::as_str)
+    }
+
+    /// Positional argument after the command, counting from 0
+    pub fn arg(&self, index: usize, name: &str) -> AoriResult<&str> {
+        self.positional
+            .get(index + 1)
+            .map(String::as_str)
+            .ok_or_else(|| AoriError::Config(format!("missing <{}>", name)))
+    }
+
+    pub fn flag(&self, name: &str) -> Option<&str> {
+        self.flags.get(name).map(String::as_str)
+    }
+
+    pub fn required(&self, name: &str) -> AoriResult<&str> {
+        self.flag(name).ok_or_else(|| AoriError::Config(format!("missing --{}", name)))
+    }
+
+    pub fn parsed<T: FromStr>(&self, name: &str) -> AoriResult<Option<T>> {
+        self.flag(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| AoriError::Config(format!("invalid --{} {}", name, value)))
+            })
+            .transpose()
+    }
+
+    pub fn switch(&self, name: &str) -> bool {
+        self.switches.iter().any(|switch| switch == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Args {
+        Args::parse(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn parses_flags_switches_and_positionals() {
+        let args = args("make order.json --public --seat-id 3 --tag=bot --output json");
+        assert_eq!(args.command(), Some("make"));
+        assert_eq!(args.arg(0, "order").unwrap(), "order.json");
+        assert!(args.arg(1, "extra").is_err());
+        assert!(args.switch("public"));
+        assert_eq!(args.parsed::<i64>("seat-id").unwrap(), Some(3));
+        assert_eq!(args.flag("tag"), Some("bot"));
+        assert_eq!(args.required("output").unwrap(), "json");
+        assert!(args.required("api-key").is_err());
+        assert!(args.parsed::<u64>("tag").is_err());
+    }
+}
@@ -0,0 +1,309 @@
+// aori command line
+// talks to the backend and data provider, and signs, hashes and decodes orders offline
+
+mod args;
+mod output;
+
+use std::{env, fs, io::Read};
+
+use alloy_primitives::{hex, Address, B256, U256};
+use aori_rs::{
+    config::DeploymentConfig,
+    contract::decode_settle_orders,
+    filter::FeedFilter,
+    get_order_hash, get_order_signer,
+    http_client::{AoriHttpClient, HttpClientConfig},
+    relay::{AoriRelayRpcClient, RelayFilter},
+    shared_types::{Query, SortBy},
+    sign_order_with_wallet, AoriBackendRpcClient, AoriCancelAllOrdersParams, AoriCancelOrderParams,
+    AoriError, AoriMakeOrderParams, AoriOrder, AoriPingParams, AoriRequestQuoteParams, AoriResult,
+    AoriTakeOrderParams, ProviderClient, ViewOrderbookQuery,
+};
+use ethers::signers::{LocalWallet, Signer};
+use jsonrpsee::ws_client::WsClientBuilder;
+use serde_json::json;
+
+use args::Args;
+use output::Output;
+
+const USAGE: &str = "\
+usage: aori <command> [args] [--output table|json] [--env mainnet|testnet|local_fork]
+            [--config <file>] [--keystore <file>]
+
+commands:
+  ping                                   ping the backend and the data provider
+  quote --input-token <address> --output-token <address> --input-amount <amount>
+        --chain-id <id> [--output-amount <amount>]
+                                         request a quote
+  make <order.json> [--public] [--seat-id <id>] [--tag <tag>]
+                                         sign and submit a maker order
+  take <order.json> --order-hash <hash> [--seat-id <id>]
+                                         sign and submit a taker order against <hash>
+  cancel <order-hash>                    cancel an order
+  cancel --all [--tag <tag>]             cancel every order for the API key
+  orderbook --chain-id <id> --base <address> --quote <address> [--limit <n>]
+        [--offerer <address>] [--zone <address>] [--sort-by <createdAtDesc|rateDesc|..>]
+                                         view the orderbook
+  tail [--feed <url>] [--chain-id <id>]  print feed events as they arrive
+  hash <order.json>                      print an order's hash
+  sign <order.json>                      hash and sign an order
+  recover <order.json> <signature>       recover the signer of an order signature
+  decode <calldata>                      decode settleOrders calldata
+
+<order.json> may be - to read stdin.
+
+environment:
+  AORI_PRIVATE_KEY                       signing key
+  AORI_KEYSTORE, AORI_KEYSTORE_PASSWORD  encrypted keystore, used instead of AORI_PRIVATE_KEY;
+                                         --keystore overrides AORI_KEYSTORE
+  AORI_API_KEY                           API key, or pass --api-key
+  AORI_FEED_URL                          websocket feed for tail, or pass --feed
+  AORI_CONFIG, AORI_ENVIRONMENT, ...     deployment, see DeploymentConfig::from_env";
+
+const ORDER_COLUMNS: &[&str] = &[
+    "orderHash",
+    "offerer",
+    "inputToken",
+    "inputAmount",
+    "outputToken",
+    "outputAmount",
+    "rate",
+    "isActive",
+];
+
+const EVENT_COLUMNS: &[&str] = &[
+    "type",
+    "data.orderHash",
+    "data.inputToken",
+    "data.inputAmount",
+    "data.outputToken",
+    "data.outputAmount",
+];
+
+#[tokio::main]
+async fn main() {
+    dotenv::dotenv().ok();
+
+    let args = Args::parse(env::args().skip(1));
+    if args.command().is_none() || args.switch("help") {
+        println!("{}", USAGE);
+        return;
+    }
+
+    if let Err(e) = run(&args).await {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(args: &Args) -> AoriResult<()> {
+    let output: Output = args.parsed("output")?.unwrap_or_default();
+
+    match args.command().unwrap_or_default() {
+        "ping" => {
+            let config = config(args)?;
+            let backend = backend_client(&config)?
+                .ping(AoriPingParams::default())
+                .await
+                .map_err(AoriError::from_backend)?;
+            let provider = ProviderClient::from_config(&config).ping().await?;
+            output.print(&json!({ "backend": backend, "provider": provider }))
+        }
+        "quote" => {
+            let params = AoriRequestQuoteParams {
+                input_token: args.required("input-token")?.to_string(),
+                output_token: args.required("output-token")?.to_string(),
+                input_amount: args.required("input-amount")?.to_string(),
+                output_amount: args.flag("output-amount").map(String::from),
+                chain_id: required(args, "chain-id")?,
+                api_key: api_key(args)?,
+            };
+            let quote = backend_client(&config(args)?)?
+                .request_quote(params)
+                .await
+                .map_err(AoriError::from_backend)?;
+            output.print(&quote)
+        }
+        "make" => {
+            let order = read_order(args.arg(0, "order.json")?)?;
+            let signature = sign_order_with_wallet(order.clone(), &wallet(args)?).await?;
+            let params = AoriMakeOrderParams {
+                order,
+                signature,
+                is_public: Some(args.switch("public")),
+                seat_id: args.parsed("seat-id")?,
+                tag: args.flag("tag").map(String::from),
+                api_key: Some(api_key(args)?),
+            };
+            let order = backend_client(&config(args)?)?
+                .make_order(params)
+                .await
+                .map_err(AoriError::from_backend)?;
+            output.print(&order)
+        }
+        "take" => {
+            let order = read_order(args.arg(0, "order.json")?)?;
+            let order_hash: B256 = required(args, "order-hash")?;
+            let signature = sign_order_with_wallet(order.clone(), &wallet(args)?).await?;
+            let params = AoriTakeOrderParams {
+                order,
+                signature,
+                order_hash: order_hash.to_string(),
+                seat_id: args.parsed("seat-id")?,
+                signed_approval_tx: None,
+            };
+            let result = backend_client(&config(args)?)?
+                .take_order(params)
+                .await
+                .map_err(AoriError::from_backend)?;
+            output.print(&result)
+        }
+        "cancel" => {
+            let backend = backend_client(&config(args)?)?;
+            if args.switch("all") {
+                let params = AoriCancelAllOrdersParams {
+                    api_key: api_key(args)?,
+                    tag: args.flag("tag").map(String::from),
+                };
+                backend.cancel_all_orders(params).await.map_err(AoriError::from_backend)?;
+                return output.print(&json!({ "cancelled": "all" }));
+            }
+
+            let order_hash: B256 = parse(args.arg(0, "order-hash")?, "order hash")?;
+            let params = AoriCancelOrderParams {
+                order_hash: order_hash.to_string(),
+                api_key: api_key(args)?,
+            };
+            let result = backend.cancel_order(params).await.map_err(AoriError::from_backend)?;
+            output.print(&result)
+        }
+        "orderbook" => {
+            let query = ViewOrderbookQuery {
+                chainId: U256::from(required::<u64>(args, "chain-id")?),
+                query: Query { base: required(args, "base")?, quote: required(args, "quote")? },
+                limit: U256::from(args.parsed::<u64>("limit")?.unwrap_or(20)),
+                orderHash: B256::ZERO,
+                offerer: args.parsed("offerer")?.unwrap_or(Address::ZERO),
+                sortBy: match args.flag("sort-by") {
+                    Some(sort_by) => serde_json::from_value(json!(sort_by))?,
+                    None => SortBy::createdAtDesc,
+                },
+                inputAmount: U256::ZERO,
+                outputAmount: U256::ZERO,
+                zone: args.parsed("zone")?.unwrap_or(Address::ZERO),
+                allowStaleQuotes: false,
+            };
+            let orders = backend_client(&config(args)?)?
+                .view_orderbook(query)
+                .await
+                .map_err(AoriError::from_backend)?;
+            output.print_columns(&orders, ORDER_COLUMNS)
+        }
+        "tail" => {
+            let url = match args.flag("feed") {
+                Some(url) => url.to_string(),
+                None => env::var("AORI_FEED_URL")
+                    .map_err(|_| AoriError::Config("pass --feed or set AORI_FEED_URL".into()))?,
+            };
+            let filter =
+                args.parsed::<u64>("chain-id")?.map(FeedFilter::ChainId).map(RelayFilter::from);
+
+            let client = WsClientBuilder::default()
+                .build(&url)
+                .await
+                .map_err(|e| AoriError::Transport(e.to_string()))?;
+            let mut feed = client.subscribe_feed(filter).await.map_err(AoriError::from_backend)?;
+            while let Some(event) = feed.next().await {
+                let event = event.map_err(|e| AoriError::Encoding(e.to_string()))?;
+                output.print_line(&event, EVENT_COLUMNS)?;
+            }
+            Ok(())
+        }
+        "hash" => {
+            let order = read_order(args.arg(0, "order.json")?)?;
+            output.print(&get_order_hash(order))
+        }
+        "sign" => {
+            let order = read_order(args.arg(0, "order.json")?)?;
+            let wallet = wallet(args)?;
+            let signature = sign_order_with_wallet(order.clone(), &wallet).await?;
+            output.print(&json!({
+                "orderHash": get_order_hash(order),
+                "signature": signature,
+                "signer": format!("{:?}", wallet.address()),
+            }))
+        }
+        "recover" => {
+            let order = read_order(args.arg(0, "order.json")?)?;
+            let signer = get_order_signer(order.clone(), args.arg(1, "signature")?).await?;
+            output.print(&json!({
+                "orderHash": get_order_hash(order),
+                "signer": format!("{:?}", signer),
+            }))
+        }
+        "decode" => {
+            let calldata = hex::decode(args.arg(0, "calldata")?)?;
+            output.print(&decode_settle_orders(&calldata)?)
+        }
+        command => Err(AoriError::Config(format!("unknown command {}, see --help", command))),
+    }
+}
+
+/// `--config`, then `--env`, then `DeploymentConfig::from_env`
+fn config(args: &Args) -> AoriResult<DeploymentConfig> {
+    if let Some(path) = args.flag("config") {
+        return DeploymentConfig::from_file(path)?.with_env_overrides();
+    }
+    if let Some(environment) = args.parsed("env")? {
+        return DeploymentConfig::for_environment(environment).with_env_overrides();
+    }
+    DeploymentConfig::from_env()
+}
+
+fn backend_client(config: &DeploymentConfig) -> AoriResult<AoriHttpClient> {
+    HttpClientConfig::new().build(&config.backend_url)
+}
+
+/// Decrypts `AORI_KEYSTORE` if set, otherwise reads `AORI_PRIVATE_KEY`
+fn wallet(args: &Args) -> AoriResult<LocalWallet> {
+    let keystore =
+        args.flag("keystore").map(String::from).or_else(|| env::var("AORI_KEYSTORE").ok());
+    if let Some(keystore) = keystore {
+        let password = env::var("AORI_KEYSTORE_PASSWORD")
+            .map_err(|_| AoriError::Config("AORI_KEYSTORE_PASSWORD is not set".into()))?;
+        return Ok(LocalWallet::decrypt_keystore(keystore, password)?);
+    }
+
+    let key = env::var("AORI_PRIVATE_KEY")
+        .map_err(|_| AoriError::Config("set AORI_PRIVATE_KEY or AORI_KEYSTORE".into()))?;
+    Ok(key.parse()?)
+}
+
+fn api_key(args: &Args) -> AoriResult<String> {
+    match args.flag("api-key") {
+        Some(api_key) => Ok(api_key.to_string()),
+        None => env::var("AORI_API_KEY")
+            .map_err(|_| AoriError::Config("pass --api-key or set AORI_API_KEY".into())),
+    }
+}
+
+fn required<T: std::str::FromStr>(args: &Args, name: &str) -> AoriResult<T> {
+    parse(args.required(name)?, name)
+}
+
+fn parse<T: std::str::FromStr>(value: &str, name: &str) -> AoriResult<T> {
+    value.parse().map_err(|_| AoriError::Config(format!("invalid {} {}", name, value)))
+}
+
+fn read_order(path: &str) -> AoriResult<AoriOrder> {
+    let contents = if path == "-" {
+        let mut contents = String::new();
+        std::io::stdin()
+            .read_to_string(&mut contents)
+            .map_err(|e| AoriError::Config(format!("stdin: {}", e)))?;
+        contents
+    } else {
+        fs::read_to_string(path).map_err(|e| AoriError::Config(format!("{}: {}", path, e)))?
+    };
+    Ok(serde_json::from_str(&contents)?)
+}
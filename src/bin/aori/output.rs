@@ -0,0 +1,148 @@
+// output formatting
+// pretty JSON, or plain text tables for humans
+
+use std::str::FromStr;
+
+use aori_rs::{AoriError, AoriResult};
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Output {
+    Json,
+    #[default]
+    Table,
+}
+
+impl FromStr for Output {
+    type Err = AoriError;
+
+    fn from_str(s: &str) -> AoriResult<Self> {
+        match s {
+            "json" => Ok(Output::Json),
+            "table" => Ok(Output::Table),
+            _ => Err(AoriError::Config(format!("unknown output format {}", s))),
+        }
+    }
+}
+
+impl Output {
+    pub fn print(&self, value: &impl Serialize) -> AoriResult<()> {
+        self.print_columns(value, &[])
+    }
+
+    /// Like `print`, showing only `columns` when the value is a list of objects
+    pub fn print_columns(&self, value: &impl Serialize, columns: &[&str]) -> AoriResult<()> {
+        let value = serde_json::to_value(value)?;
+        match self {
+            Output::Json => println!("{}", serde_json::to_string_pretty(&value)?),
+            Output::Table => println!("{}", table(&value, columns)),
+        }
+        Ok(())
+    }
+
+    /// One line per item, for streams
+    pub fn print_line(&self, value: &impl Serialize, columns: &[&str]) -> AoriResult<()> {
+        let value = serde_json::to_value(value)?;
+        match self {
+            Output::Json => println!("{}", serde_json::to_string(&value)?),
+            Output::Table => {
+                let cells: Vec<String> = columns
+                    .iter()
+                    .map(|column| cell(lookup(&value, column).unwrap_or(&Value::Null)))
+                    .collect();
+                println!("{}", cells.join("  "));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Finds `path` in `value`, where `path` is a dot separated list of object keys
+pub fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(value, |value, key| value.get(key))
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Lists of objects become one row per item, objects become key/value rows and anything else
+/// is printed as is
+fn table(value: &Value, columns: &[&str]) -> String {
+    let rows: Vec<Vec<String>> = match value {
+        Value::Array(items) if items.iter().all(Value::is_object) && !items.is_empty() => {
+            let columns: Vec<String> = if columns.is_empty() {
+                items[0].as_object().into_iter().flat_map(|object| object.keys().cloned()).collect()
+            } else {
+                columns.iter().map(|column| column.to_string()).collect()
+            };
+            let mut rows = vec![columns.clone()];
+            rows.extend(items.iter().map(|item| {
+                columns
+                    .iter()
+                    .map(|column| cell(lookup(item, column).unwrap_or(&Value::Null)))
+                    .collect()
+            }));
+            rows
+        }
+        Value::Object(object) => {
+            object.iter().map(|(key, value)| vec![key.clone(), cell(value)]).collect()
+        }
+        value => return cell(value),
+    };
+
+    // an empty object, or items without keys, leave nothing to show
+    if rows.first().is_none_or(Vec::is_empty) {
+        return String::new();
+    }
+
+    let widths: Vec<usize> = (0..rows[0].len())
+        .map(|i| rows.iter().map(|row| row[i].len()).max().unwrap_or(0))
+        .collect();
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn formats_tables() {
+        let orders = json!([
+            { "orderHash": "0x01", "order": { "inputAmount": "1000" }, "isActive": true },
+            { "orderHash": "0x0203", "order": { "inputAmount": "5" }, "isActive": false },
+        ]);
+        assert_eq!(
+            table(&orders, &["orderHash", "order.inputAmount", "isActive"]),
+            "orderHash  order.inputAmount  isActive\n\
+             0x01       1000               true\n\
+             0x0203     5                  false"
+        );
+        assert_eq!(table(&json!({ "signer": "0xab", "v": 27 }), &[]), "signer  0xab\nv       27");
+        assert_eq!(table(&json!("aori_pong"), &[]), "aori_pong");
+    }
+
+    #[test]
+    fn formats_empty_tables() {
+        assert_eq!(table(&json!({}), &[]), "");
+        assert_eq!(table(&json!([{}, {}]), &[]), "");
+        assert_eq!(table(&json!([]), &[]), "[]");
+    }
+}
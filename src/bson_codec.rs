@@ -0,0 +1,533 @@
+// bson codecs
+// lossless documents for the types the SDK persists
+
+use alloy_primitives::{hex, Address, B256, U256};
+use bson::{doc, Bson, Document};
+use mongodb::IndexModel;
+
+use super::{
+    error::{AoriError, AoriResult},
+    rate::Rate,
+    AoriMatchingDetails, AoriOrder, DetailsToExecute, OrderView, SettledMatch,
+};
+
+/// Converts a type to and from its MongoDB document.
+///
+/// Addresses and hashes are lowercase `0x` hex so they index and compare as plain strings,
+/// chain ids and timestamps are `Int64`, and amounts are decimal strings since a `U256` doesn't
+/// fit any BSON number. Decoding also accepts the numbers and mixed-case hex older documents
+/// were written with
+pub trait BsonCodec: Sized {
+    fn to_document(&self) -> AoriResult<Document>;
+
+    fn from_document(document: &Document) -> AoriResult<Self>;
+
+    /// Indexes for a collection of these documents
+    fn indexes() -> Vec<IndexModel> {
+        Vec::new()
+    }
+}
+
+pub(crate) fn encode_address(address: Address) -> Bson {
+    Bson::String(hex::encode_prefixed(address))
+}
+
+pub(crate) fn encode_hash(hash: B256) -> Bson {
+    Bson::String(hex::encode_prefixed(hash))
+}
+
+pub(crate) fn encode_amount(amount: U256) -> Bson {
+    Bson::String(amount.to_string())
+}
+
+/// For chain ids, timestamps and other values the backend keeps as 64-bit numbers
+pub(crate) fn encode_int(value: U256) -> AoriResult<Bson> {
+    if value > U256::from(i64::MAX) {
+        return Err(AoriError::Encoding(format!(
+            "expected a non-negative 64-bit integer, found {}",
+            value
+        )));
+    }
+    Ok(Bson::Int64(value.to::<i64>()))
+}
+
+fn index(keys: Document) -> IndexModel {
+    IndexModel::builder().keys(keys).build()
+}
+
+/// Typed field access with errors that name the missing or malformed field
+struct Fields<'a>(&'a Document);
+
+impl<'a> Fields<'a> {
+    fn error(&self, key: &str, expected: &str) -> AoriError {
+        match self.0.get(key) {
+            Some(value) => AoriError::Encoding(format!(
+                "field `{}`: expected {}, found {:?}",
+                key,
+                expected,
+                value.element_type()
+            )),
+            None => AoriError::Encoding(format!("missing field `{}`", key)),
+        }
+    }
+
+    fn has(&self, key: &str) -> bool {
+        !matches!(self.0.get(key), None | Some(Bson::Null))
+    }
+
+    fn str(&self, key: &str) -> AoriResult<&'a str> {
+        match self.0.get(key) {
+            Some(Bson::String(value)) => Ok(value),
+            _ => Err(self.error(key, "a string")),
+        }
+    }
+
+    fn string(&self, key: &str) -> AoriResult<String> {
+        self.str(key).map(str::to_owned)
+    }
+
+    fn bool(&self, key: &str) -> AoriResult<bool> {
+        match self.0.get(key) {
+            Some(Bson::Boolean(value)) => Ok(*value),
+            _ => Err(self.error(key, "a boolean")),
+        }
+    }
+
+    fn address(&self, key: &str) -> AoriResult<Address> {
+        self.str(key)?.parse().map_err(|_| self.error(key, "an address"))
+    }
+
+    fn hash(&self, key: &str) -> AoriResult<B256> {
+        self.str(key)?.parse().map_err(|_| self.error(key, "a 32 byte hash"))
+    }
+
+    fn bytes(&self, key: &str) -> AoriResult<Vec<u8>> {
+        match self.0.get(key) {
+            Some(Bson::String(value)) => hex::decode(value).map_err(|_| self.error(key, "hex")),
+            Some(Bson::Binary(binary)) => Ok(binary.bytes.clone()),
+            _ => Err(self.error(key, "hex or binary")),
+        }
+    }
+
+    fn amount(&self, key: &str) -> AoriResult<U256> {
+        match self.0.get(key) {
+            Some(Bson::String(value)) => {
+                U256::from_str_radix(value, 10).map_err(|_| self.error(key, "a decimal amount"))
+            }
+            Some(Bson::Int32(value)) if *value >= 0 => Ok(U256::from(*value)),
+            Some(Bson::Int64(value)) if *value >= 0 => Ok(U256::from(*value)),
+            _ => Err(self.error(key, "a decimal amount")),
+        }
+    }
+
+    fn int(&self, key: &str) -> AoriResult<U256> {
+        match self.amount(key) {
+            Ok(value) if value <= U256::from(i64::MAX) => Ok(value),
+            _ => Err(self.error(key, "a non-negative 64-bit integer")),
+        }
+    }
+
+    fn document(&self, key: &str) -> AoriResult<&'a Document> {
+        match self.0.get(key) {
+            Some(Bson::Document(document)) => Ok(document),
+            _ => Err(self.error(key, "a document")),
+        }
+    }
+
+    fn optional<T>(
+        &self,
+        key: &str,
+        decode: impl FnOnce(&Self, &str) -> AoriResult<T>,
+    ) -> AoriResult<Option<T>> {
+        if self.has(key) {
+            decode(self, key).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl BsonCodec for AoriOrder {
+    fn to_document(&self) -> AoriResult<Document> {
+        Ok(doc! {
+            "offerer": encode_address(self.offerer),
+            "inputToken": encode_address(self.inputToken),
+            "inputAmount": encode_amount(self.inputAmount),
+            "inputChainId": encode_int(self.inputChainId)?,
+            "inputZone": encode_address(self.inputZone),
+            "outputToken": encode_address(self.outputToken),
+            "outputAmount": encode_amount(self.outputAmount),
+            "outputChainId": encode_int(self.outputChainId)?,
+            "outputZone": encode_address(self.outputZone),
+            "startTime": encode_amount(self.startTime),
+            "endTime": encode_amount(self.endTime),
+            "salt": encode_amount(self.salt),
+            "counter": encode_amount(self.counter),
+            "toWithdraw": self.toWithdraw,
+        })
+    }
+
+    fn from_document(document: &Document) -> AoriResult<Self> {
+        let fields = Fields(document);
+        Ok(AoriOrder {
+            offerer: fields.address("offerer")?,
+            inputToken: fields.address("inputToken")?,
+            inputAmount: fields.amount("inputAmount")?,
+            inputChainId: fields.int("inputChainId")?,
+            inputZone: fields.address("inputZone")?,
+            outputToken: fields.address("outputToken")?,
+            outputAmount: fields.amount("outputAmount")?,
+            outputChainId: fields.int("outputChainId")?,
+            outputZone: fields.address("outputZone")?,
+            startTime: fields.amount("startTime")?,
+            endTime: fields.amount("endTime")?,
+            salt: fields.amount("salt")?,
+            counter: fields.amount("counter")?,
+            toWithdraw: fields.bool("toWithdraw")?,
+        })
+    }
+}
+
+impl BsonCodec for AoriMatchingDetails {
+    fn to_document(&self) -> AoriResult<Document> {
+        Ok(doc! {
+            "makerOrder": self.makerOrder.to_document()?,
+            "takerOrder": self.takerOrder.to_document()?,
+            "makerSignature": hex::encode_prefixed(&self.makerSignature),
+            "takerSignature": hex::encode_prefixed(&self.takerSignature),
+            "blockDeadline": encode_int(self.blockDeadline)?,
+            "seatNumber": encode_int(self.seatNumber)?,
+            "seatHolder": encode_address(self.seatHolder),
+            "seatPercentOfFees": encode_int(self.seatPercentOfFees)?,
+        })
+    }
+
+    fn from_document(document: &Document) -> AoriResult<Self> {
+        let fields = Fields(document);
+        Ok(AoriMatchingDetails {
+            makerOrder: AoriOrder::from_document(fields.document("makerOrder")?)?,
+            takerOrder: AoriOrder::from_document(fields.document("takerOrder")?)?,
+            makerSignature: fields.bytes("makerSignature")?.into(),
+            takerSignature: fields.bytes("takerSignature")?.into(),
+            blockDeadline: fields.int("blockDeadline")?,
+            seatNumber: fields.int("seatNumber")?,
+            seatHolder: fields.address("seatHolder")?,
+            seatPercentOfFees: fields.int("seatPercentOfFees")?,
+        })
+    }
+}
+
+/// Keyed by order hash. `rate` keeps the exact fraction next to a double for sorting
+impl BsonCodec for OrderView {
+    fn to_document(&self) -> AoriResult<Document> {
+        Ok(doc! {
+            "_id": encode_hash(self.order_hash),
+            "orderHash": encode_hash(self.order_hash),
+            "offerer": encode_address(self.offerer),
+            "order": self.order.to_document()?,
+            "signature": &self.signature,
+            "inputToken": encode_address(self.input_token),
+            "inputAmount": encode_amount(self.input_amount),
+            "inputChainId": encode_int(self.input_chain_id)?,
+            "inputZone": encode_address(self.input_zone),
+            "outputToken": encode_address(self.output_token),
+            "outputAmount": encode_amount(self.output_amount),
+            "outputChainId": encode_int(self.output_chain_id)?,
+            "outputZone": encode_address(self.output_zone),
+            "rate": {
+                "value": self.rate.to_f64(),
+                "numerator": encode_amount(self.rate.numerator()),
+                "denominator": encode_amount(self.rate.denominator()),
+            },
+            "createdAt": encode_int(self.created_at)?,
+            "lastUpdatedAt": encode_int(self.last_updated_at)?,
+            "isActive": self.is_active,
+            "isPublic": self.is_public,
+        })
+    }
+
+    fn from_document(document: &Document) -> AoriResult<Self> {
+        let fields = Fields(document);
+        let rate = match document.get("rate") {
+            Some(Bson::Document(rate)) => {
+                let rate = Fields(rate);
+                Rate::from_ratio(rate.amount("numerator")?, rate.amount("denominator")?)
+            }
+            // older documents kept the backend's bare number, or its decimal string
+            Some(rate @ (Bson::Double(_) | Bson::Int32(_) | Bson::Int64(_) | Bson::String(_))) => {
+                bson::from_bson(rate.clone())?
+            }
+            _ => return Err(fields.error("rate", "a rate")),
+        };
+
+        Ok(OrderView {
+            order_hash: fields.hash("orderHash")?,
+            offerer: fields.address("offerer")?,
+            order: AoriOrder::from_document(fields.document("order")?)?,
+            signature: fields.string("signature")?,
+            input_token: fields.address("inputToken")?,
+            input_amount: fields.amount("inputAmount")?,
+            input_chain_id: fields.int("inputChainId")?,
+            input_zone: fields.address("inputZone")?,
+            output_token: fields.address("outputToken")?,
+            output_amount: fields.amount("outputAmount")?,
+            output_chain_id: fields.int("outputChainId")?,
+            output_zone: fields.address("outputZone")?,
+            rate,
+            created_at: fields.int("createdAt")?,
+            last_updated_at: fields.int("lastUpdatedAt")?,
+            is_active: fields.bool("isActive")?,
+            is_public: fields.bool("isPublic")?,
+        })
+    }
+
+    fn indexes() -> Vec<IndexModel> {
+        vec![
+            index(doc! { "offerer": 1 }),
+            index(doc! { "inputToken": 1, "outputToken": 1, "rate.value": -1 }),
+            index(doc! { "inputChainId": 1 }),
+            index(doc! { "isActive": 1, "createdAt": 1 }),
+        ]
+    }
+}
+
+/// Keyed by matching hash
+impl BsonCodec for SettledMatch {
+    fn to_document(&self) -> AoriResult<Document> {
+        let mut document = doc! {
+            "_id": encode_hash(self.matching_hash),
+            "makerOrderHash": encode_hash(self.maker_order_hash),
+            "takerOrderHash": encode_hash(self.taker_order_hash),
+            "maker": encode_address(self.maker),
+            "taker": encode_address(self.taker),
+            "inputChainId": encode_int(self.input_chain_id)?,
+            "outputChainId": encode_int(self.output_chain_id)?,
+            "inputZone": encode_address(self.input_zone),
+            "outputZone": encode_address(self.output_zone),
+            "inputToken": encode_address(self.input_token),
+            "outputToken": encode_address(self.output_token),
+            "inputAmount": encode_amount(self.input_amount),
+            "outputAmount": encode_amount(self.output_amount),
+            "matchingHash": encode_hash(self.matching_hash),
+        };
+        if let Some(transaction_hash) = self.transaction_hash {
+            document.insert("transactionHash", encode_hash(transaction_hash));
+        }
+        if let Some(block_number) = self.block_number {
+            document.insert("blockNumber", encode_int(block_number)?);
+        }
+        if let Some(timestamp) = self.timestamp {
+            document.insert("timestamp", encode_int(timestamp)?);
+        }
+        Ok(document)
+    }
+
+    fn from_document(document: &Document) -> AoriResult<Self> {
+        let fields = Fields(document);
+        Ok(SettledMatch {
+            maker_order_hash: fields.hash("makerOrderHash")?,
+            taker_order_hash: fields.hash("takerOrderHash")?,
+            maker: fields.address("maker")?,
+            taker: fields.address("taker")?,
+            input_chain_id: fields.int("inputChainId")?,
+            output_chain_id: fields.int("outputChainId")?,
+            input_zone: fields.address("inputZone")?,
+            output_zone: fields.address("outputZone")?,
+            input_token: fields.address("inputToken")?,
+            output_token: fields.address("outputToken")?,
+            input_amount: fields.amount("inputAmount")?,
+            output_amount: fields.amount("outputAmount")?,
+            matching_hash: fields.hash("matchingHash")?,
+            transaction_hash: fields.optional("transactionHash", Fields::hash)?,
+            block_number: fields.optional("blockNumber", Fields::int)?,
+            timestamp: fields.optional("timestamp", Fields::int)?,
+        })
+    }
+
+    fn indexes() -> Vec<IndexModel> {
+        vec![
+            index(doc! { "makerOrderHash": 1 }),
+            index(doc! { "takerOrderHash": 1 }),
+            index(doc! { "maker": 1 }),
+            index(doc! { "taker": 1 }),
+            index(doc! { "inputChainId": 1, "blockNumber": -1 }),
+        ]
+    }
+}
+
+/// Keyed by matching hash
+impl BsonCodec for DetailsToExecute {
+    fn to_document(&self) -> AoriResult<Document> {
+        let mut document = doc! {
+            "_id": encode_hash(self.matching_hash),
+            "matchingHash": encode_hash(self.matching_hash),
+            "matching": self.matching.to_document()?,
+            "matchingSignature": &self.matching_signature,
+            "makerOrderHash": encode_hash(self.maker_order_hash),
+            "makerChainId": encode_int(self.maker_chain_id)?,
+            "makerZone": encode_address(self.maker_zone),
+            "takerOrderHash": encode_hash(self.taker_order_hash),
+            "takerChainId": encode_int(self.taker_chain_id)?,
+            "takerZone": encode_address(self.taker_zone),
+            "chainId": encode_int(self.chain_id)?,
+            "to": encode_address(self.to),
+            "value": encode_amount(self.value),
+            "data": hex::encode_prefixed(&self.data),
+            "maker": encode_address(self.maker),
+            "taker": encode_address(self.taker),
+            "inputToken": encode_address(self.input_token),
+            "inputAmount": encode_amount(self.input_amount),
+            "outputToken": encode_address(self.output_token),
+            "outputAmount": encode_amount(self.output_amount),
+        };
+        if let Some(signature) = &self.taker_permit_signature {
+            document.insert("takerPermitSignature", signature);
+        }
+        Ok(document)
+    }
+
+    fn from_document(document: &Document) -> AoriResult<Self> {
+        let fields = Fields(document);
+        Ok(DetailsToExecute {
+            matching_hash: fields.hash("matchingHash")?,
+            matching: AoriMatchingDetails::from_document(fields.document("matching")?)?,
+            matching_signature: fields.string("matchingSignature")?,
+            maker_order_hash: fields.hash("makerOrderHash")?,
+            maker_chain_id: fields.int("makerChainId")?,
+            maker_zone: fields.address("makerZone")?,
+            taker_order_hash: fields.hash("takerOrderHash")?,
+            taker_chain_id: fields.int("takerChainId")?,
+            taker_zone: fields.address("takerZone")?,
+            chain_id: fields.int("chainId")?,
+            to: fields.address("to")?,
+            value: fields.amount("value")?,
+            data: fields.bytes("data")?,
+            taker_permit_signature: fields.optional("takerPermitSignature", Fields::string)?,
+            maker: fields.address("maker")?,
+            taker: fields.address("taker")?,
+            input_token: fields.address("inputToken")?,
+            input_amount: fields.amount("inputAmount")?,
+            output_token: fields.address("outputToken")?,
+            output_amount: fields.amount("outputAmount")?,
+        })
+    }
+
+    fn indexes() -> Vec<IndexModel> {
+        vec![
+            index(doc! { "makerOrderHash": 1 }),
+            index(doc! { "takerOrderHash": 1 }),
+            index(doc! { "maker": 1 }),
+            index(doc! { "taker": 1 }),
+            index(doc! { "chainId": 1 }),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{to_order_view, AoriFeedEvents};
+
+    const ORDER_TO_EXECUTE: &str = include_str!("../tests/fixtures/order_to_execute.json");
+
+    fn order_view() -> OrderView {
+        let order = AoriOrder {
+            offerer: Address::repeat_byte(0xab),
+            inputToken: Address::repeat_byte(2),
+            inputAmount: U256::MAX,
+            inputChainId: U256::from(42161),
+            outputToken: Address::repeat_byte(3),
+            outputAmount: U256::from(2),
+            outputChainId: U256::from(42161),
+            salt: U256::from(u128::MAX),
+            ..Default::default()
+        };
+        to_order_view(order, "0x01".to_string(), true, false)
+    }
+
+    fn assert_round_trips<T: BsonCodec + serde::Serialize>(value: &T) {
+        let document = value.to_document().unwrap();
+        let decoded = T::from_document(&document).unwrap();
+        assert_eq!(serde_json::to_value(value).unwrap(), serde_json::to_value(&decoded).unwrap());
+        assert_eq!(decoded.to_document().unwrap(), document);
+    }
+
+    #[test]
+    fn round_trips_without_losing_amounts() {
+        let view = order_view();
+        assert_round_trips(&view);
+
+        let document = view.to_document().unwrap();
+        assert_eq!(document.get_str("inputAmount").unwrap(), U256::MAX.to_string());
+        assert_eq!(document.get_i64("inputChainId").unwrap(), 42161);
+        assert_eq!(document.get_str("offerer").unwrap(), format!("0x{}", "ab".repeat(20)));
+        assert_eq!(OrderView::from_document(&document).unwrap().rate, view.rate);
+
+        let AoriFeedEvents::OrderToExecute(details) =
+            crate::deserialize_aori_feed_event(ORDER_TO_EXECUTE).unwrap()
+        else {
+            panic!("fixture is not an OrderToExecute event");
+        };
+        assert_round_trips(&*details);
+
+        let settled = SettledMatch {
+            maker_order_hash: details.maker_order_hash,
+            taker_order_hash: details.taker_order_hash,
+            maker: details.maker,
+            taker: details.taker,
+            input_chain_id: details.chain_id,
+            output_chain_id: details.chain_id,
+            input_zone: details.maker_zone,
+            output_zone: details.taker_zone,
+            input_token: details.input_token,
+            output_token: details.output_token,
+            input_amount: details.input_amount,
+            output_amount: details.output_amount,
+            matching_hash: details.matching_hash,
+            transaction_hash: Some(B256::repeat_byte(1)),
+            block_number: Some(U256::from(19_000_000)),
+            timestamp: None,
+        };
+        assert_round_trips(&settled);
+        assert!(!settled.to_document().unwrap().contains_key("timestamp"));
+    }
+
+    #[test]
+    fn decodes_legacy_rates() {
+        let mut document = order_view().to_document().unwrap();
+        for (rate, expected) in [
+            (Bson::String("0.000333".into()), "0.000333"),
+            (Bson::Double(1.25), "1.25"),
+            (Bson::Int64(3000), "3000"),
+        ] {
+            document.insert("rate", rate);
+            let view = OrderView::from_document(&document).unwrap();
+            assert_eq!(view.rate, expected.parse().unwrap());
+        }
+
+        document.insert("rate", "fast");
+        assert!(OrderView::from_document(&document).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_documents() {
+        let mut document = order_view().to_document().unwrap();
+        document.insert("inputAmount", "-1");
+        let error = OrderView::from_document(&document).unwrap_err();
+        assert!(error.to_string().contains("inputAmount"), "{}", error);
+
+        document.remove("offerer");
+        let error = OrderView::from_document(&document).unwrap_err();
+        assert!(error.to_string().contains("missing field `offerer`"), "{}", error);
+
+        let mut view = order_view();
+        view.created_at = U256::MAX;
+        let error = view.to_document().unwrap_err();
+        assert!(error.to_string().contains("non-negative 64-bit integer"), "{}", error);
+
+        let mut document = order_view().to_document().unwrap();
+        document.insert("createdAt", U256::MAX.to_string());
+        let error = OrderView::from_document(&document).unwrap_err();
+        assert!(error.to_string().contains("non-negative 64-bit integer"), "{}", error);
+    }
+}
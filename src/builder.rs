@@ -2,13 +2,14 @@
 // used for signing orders and stuff
 
 use crate::{request::*, AoriOrder};
-use alloy_primitives::{keccak256, B256};
-use alloy_serde_macro::{bytes, bytes_from_string};
-use alloy_sol_types::SolValue;
-use ethers::signers::LocalWallet;
-use ethers::{abi::parse_abi_str, utils::parse_bytes32_string};
+use alloy_primitives::{keccak256, Address, B256};
+use ethers::signers::{LocalWallet, Signer};
 
-use super::get_order_hash;
+use super::{
+    error::{AoriResult, ValidationError},
+    get_order_hash,
+    seats::Seats,
+};
 
 pub struct AoriRequestBuilder {
     signer: LocalWallet,
@@ -16,11 +17,20 @@ pub struct AoriRequestBuilder {
 
 impl AoriRequestBuilder {
     /// Wraps around a Private Key / Wallet to sign off on trades
-    pub fn new(pkey_str: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let wallet: LocalWallet = pkey_str.parse().unwrap();
+    pub fn new(pkey_str: &str) -> AoriResult<Self> {
+        let wallet: LocalWallet = pkey_str.parse()?;
         Ok(AoriRequestBuilder { signer: wallet })
     }
 
+    pub fn address(&self) -> Address {
+        Address::from(self.signer.address().0)
+    }
+
+    /// Seat id to route orders through, chosen from the seats the signer holds
+    pub async fn choose_seat_id(&self, seats: &Seats) -> AoriResult<i64> {
+        Ok(seats.seat_for(self.address()).await?.seat_id as i64)
+    }
+
     /// Builds an RFQ request
     pub async fn build_rfq(
         &self,
@@ -30,11 +40,11 @@ impl AoriRequestBuilder {
         output_amount: Option<String>,
         chain_id: i64,
         api_key: String,
-    ) -> Result<AoriRequestQuoteParams, Box<dyn std::error::Error>> {
+    ) -> AoriResult<AoriRequestQuoteParams> {
         Ok(AoriRequestQuoteParams {
             input_token,
             output_token,
-            input_amount: input_amount.unwrap(),
+            input_amount: input_amount.ok_or(ValidationError::MissingInputAmount())?,
             output_amount,
             chain_id,
             api_key,
@@ -47,7 +57,7 @@ impl AoriRequestBuilder {
         is_public: bool,
         seat_id: i64,
         tag: String,
-    ) -> Result<AoriMakeOrderParams, Box<dyn std::error::Error>> {
+    ) -> AoriResult<AoriMakeOrderParams> {
         let packed = get_order_hash(order.clone());
         let hash = keccak256(packed);
 
@@ -56,28 +66,52 @@ impl AoriRequestBuilder {
 
         Ok(AoriMakeOrderParams {
             order,
-            signature: format!("0x{}", sig_hex).into(),
+            signature: format!("0x{}", sig_hex),
             is_public: Some(is_public),
             seat_id: Some(seat_id),
             tag: Some(tag),
             api_key: None,
         })
     }
+
+    /// Like `make_order`, with the seat picked from the signer's holdings
+    pub async fn make_order_with_seat(
+        &self,
+        order: AoriOrder,
+        is_public: bool,
+        tag: String,
+        seats: &Seats,
+    ) -> AoriResult<AoriMakeOrderParams> {
+        let seat_id = self.choose_seat_id(seats).await?;
+        self.make_order(order, is_public, seat_id, tag).await
+    }
+
     pub async fn take_order(
         &self,
         order: AoriOrder,
         order_hash: B256,
         seat_id: i64,
-    ) -> Result<AoriTakeOrderParams, Box<dyn std::error::Error>> {
+    ) -> AoriResult<AoriTakeOrderParams> {
         let signature = self.signer.sign_hash(order_hash.0.into())?;
         let sig_hex = hex::encode(signature.to_vec());
 
         Ok(AoriTakeOrderParams {
             order,
-            signature: format!("0x{}", sig_hex).into(),
+            signature: format!("0x{}", sig_hex),
             order_hash: order_hash.to_string(),
             seat_id: Some(seat_id),
             signed_approval_tx: None,
         })
     }
+
+    /// Like `take_order`, with the seat picked from the signer's holdings
+    pub async fn take_order_with_seat(
+        &self,
+        order: AoriOrder,
+        order_hash: B256,
+        seats: &Seats,
+    ) -> AoriResult<AoriTakeOrderParams> {
+        let seat_id = self.choose_seat_id(seats).await?;
+        self.take_order(order, order_hash, seat_id).await
+    }
 }
@@ -0,0 +1,295 @@
+use std::{fmt, time::Duration};
+
+use alloy_primitives::{address, Address, U256};
+use serde::{Deserialize, Serialize};
+
+use super::error::ValidationError;
+
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "u64", into = "u64")]
+pub enum ChainId {
+    ////////////////////////////////////////////////////////////////
+    //                          MAINNETS
+    ////////////////////////////////////////////////////////////////
+    Ethereum = 1,
+    Optimism = 10,
+    Bsc = 56,
+    Gnosis = 100,
+    Polygon = 137,
+    Fantom = 250,
+    Base = 8453,
+    Arbitrum = 42161,
+    ArbitrumNova = 42170,
+    Avalanche = 43114,
+    Blast = 81457,
+
+    ////////////////////////////////////////////////////////////////
+    //                          TESTNETS
+    ////////////////////////////////////////////////////////////////
+    AvalancheFuji = 43113,
+    BerachainArtio = 80085,
+    Sepolia = 11155111,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NativeCurrency {
+    pub name: &'static str,
+    pub symbol: &'static str,
+    pub decimals: u8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChainInfo {
+    pub id: ChainId,
+    pub name: &'static str,
+    pub native_currency: NativeCurrency,
+    /// Average time between blocks
+    pub block_time: Duration,
+    /// Blocks to wait before treating a transaction as final
+    pub confirmations: u64,
+    /// Aori V2 single-chain zones deployed on the chain
+    pub zones: &'static [Address],
+    pub testnet: bool,
+}
+
+////////////////////////////////////////////////////////////////
+//                          REGISTRY
+////////////////////////////////////////////////////////////////
+
+const AORI_V2_ZONE: Address = address!("cc1A0DA89593441571f35Dd99a0aC1856d3F1FB5");
+const AORI_V2_ZONE_2: Address = address!("6A979916234013AbA003d906e4e7136496B90AA6");
+
+const ETH: NativeCurrency = NativeCurrency { name: "Ether", symbol: "ETH", decimals: 18 };
+
+const fn chain(
+    id: ChainId,
+    name: &'static str,
+    native_currency: NativeCurrency,
+    block_time_ms: u64,
+    confirmations: u64,
+    zones: &'static [Address],
+    testnet: bool,
+) -> ChainInfo {
+    ChainInfo {
+        id,
+        name,
+        native_currency,
+        block_time: Duration::from_millis(block_time_ms),
+        confirmations,
+        zones,
+        testnet,
+    }
+}
+
+const ETHEREUM: ChainInfo = chain(ChainId::Ethereum, "Ethereum", ETH, 12_000, 3, &[], false);
+const OPTIMISM: ChainInfo =
+    chain(ChainId::Optimism, "Optimism", ETH, 2_000, 1, &[AORI_V2_ZONE], false);
+const BSC: ChainInfo = chain(
+    ChainId::Bsc,
+    "BNB Smart Chain",
+    NativeCurrency { name: "BNB", symbol: "BNB", decimals: 18 },
+    3_000,
+    15,
+    &[],
+    false,
+);
+const GNOSIS: ChainInfo = chain(
+    ChainId::Gnosis,
+    "Gnosis",
+    NativeCurrency { name: "xDAI", symbol: "XDAI", decimals: 18 },
+    5_000,
+    12,
+    &[],
+    false,
+);
+const POLYGON: ChainInfo = chain(
+    ChainId::Polygon,
+    "Polygon",
+    NativeCurrency { name: "MATIC", symbol: "MATIC", decimals: 18 },
+    2_000,
+    32,
+    &[AORI_V2_ZONE],
+    false,
+);
+const FANTOM: ChainInfo = chain(
+    ChainId::Fantom,
+    "Fantom",
+    NativeCurrency { name: "Fantom", symbol: "FTM", decimals: 18 },
+    1_000,
+    5,
+    &[],
+    false,
+);
+const BASE: ChainInfo = chain(ChainId::Base, "Base", ETH, 2_000, 1, &[AORI_V2_ZONE], false);
+const ARBITRUM: ChainInfo =
+    chain(ChainId::Arbitrum, "Arbitrum One", ETH, 250, 1, &[AORI_V2_ZONE, AORI_V2_ZONE_2], false);
+const ARBITRUM_NOVA: ChainInfo =
+    chain(ChainId::ArbitrumNova, "Arbitrum Nova", ETH, 250, 1, &[], false);
+const AVALANCHE: ChainInfo = chain(
+    ChainId::Avalanche,
+    "Avalanche",
+    NativeCurrency { name: "Avalanche", symbol: "AVAX", decimals: 18 },
+    2_000,
+    1,
+    &[],
+    false,
+);
+const BLAST: ChainInfo = chain(ChainId::Blast, "Blast", ETH, 2_000, 1, &[AORI_V2_ZONE], false);
+const AVALANCHE_FUJI: ChainInfo = chain(
+    ChainId::AvalancheFuji,
+    "Avalanche Fuji",
+    NativeCurrency { name: "Avalanche", symbol: "AVAX", decimals: 18 },
+    2_000,
+    1,
+    &[],
+    true,
+);
+const BERACHAIN_ARTIO: ChainInfo = chain(
+    ChainId::BerachainArtio,
+    "Berachain Artio",
+    NativeCurrency { name: "BERA", symbol: "BERA", decimals: 18 },
+    5_000,
+    1,
+    &[AORI_V2_ZONE],
+    true,
+);
+const SEPOLIA: ChainInfo = chain(
+    ChainId::Sepolia,
+    "Sepolia",
+    NativeCurrency { name: "Sepolia Ether", symbol: "ETH", decimals: 18 },
+    12_000,
+    2,
+    &[AORI_V2_ZONE, AORI_V2_ZONE_2],
+    true,
+);
+
+impl ChainId {
+    pub const ALL: [ChainId; 14] = [
+        ChainId::Ethereum,
+        ChainId::Optimism,
+        ChainId::Bsc,
+        ChainId::Gnosis,
+        ChainId::Polygon,
+        ChainId::Fantom,
+        ChainId::Base,
+        ChainId::Arbitrum,
+        ChainId::ArbitrumNova,
+        ChainId::Avalanche,
+        ChainId::Blast,
+        ChainId::AvalancheFuji,
+        ChainId::BerachainArtio,
+        ChainId::Sepolia,
+    ];
+
+    pub const fn id(self) -> u64 {
+        self as u64
+    }
+
+    pub const fn info(self) -> &'static ChainInfo {
+        match self {
+            ChainId::Ethereum => &ETHEREUM,
+            ChainId::Optimism => &OPTIMISM,
+            ChainId::Bsc => &BSC,
+            ChainId::Gnosis => &GNOSIS,
+            ChainId::Polygon => &POLYGON,
+            ChainId::Fantom => &FANTOM,
+            ChainId::Base => &BASE,
+            ChainId::Arbitrum => &ARBITRUM,
+            ChainId::ArbitrumNova => &ARBITRUM_NOVA,
+            ChainId::Avalanche => &AVALANCHE,
+            ChainId::Blast => &BLAST,
+            ChainId::AvalancheFuji => &AVALANCHE_FUJI,
+            ChainId::BerachainArtio => &BERACHAIN_ARTIO,
+            ChainId::Sepolia => &SEPOLIA,
+        }
+    }
+
+    pub const fn is_testnet(self) -> bool {
+        self.info().testnet
+    }
+
+    /// Whether Aori has zones deployed on the chain
+    pub const fn is_supported(self) -> bool {
+        !self.info().zones.is_empty()
+    }
+
+    /// Chains Aori has zones deployed on
+    pub fn supported() -> impl Iterator<Item = ChainId> {
+        ChainId::ALL.into_iter().filter(|chain| chain.is_supported())
+    }
+
+    /// Parses a chain id from an order, failing if the chain is unknown or unsupported
+    pub fn supported_from(chain_id: U256) -> Result<ChainId, ValidationError> {
+        let chain = ChainId::try_from(chain_id)?;
+        if !chain.is_supported() {
+            return Err(ValidationError::UnsupportedChainId(chain_id));
+        }
+        Ok(chain)
+    }
+}
+
+impl TryFrom<u64> for ChainId {
+    type Error = ValidationError;
+
+    fn try_from(chain_id: u64) -> Result<Self, Self::Error> {
+        ChainId::ALL
+            .into_iter()
+            .find(|chain| chain.id() == chain_id)
+            .ok_or(ValidationError::UnsupportedChainId(U256::from(chain_id)))
+    }
+}
+
+impl TryFrom<U256> for ChainId {
+    type Error = ValidationError;
+
+    fn try_from(chain_id: U256) -> Result<Self, Self::Error> {
+        u64::try_from(chain_id)
+            .map_err(|_| ValidationError::UnsupportedChainId(chain_id))
+            .and_then(ChainId::try_from)
+    }
+}
+
+impl From<ChainId> for u64 {
+    fn from(chain: ChainId) -> Self {
+        chain.id()
+    }
+}
+
+impl fmt::Display for ChainId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.info().name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chain_ids_convert_both_ways() {
+        for chain in ChainId::ALL {
+            assert_eq!(ChainId::try_from(chain.id()).unwrap(), chain);
+            assert_eq!(ChainId::try_from(U256::from(chain.id())).unwrap(), chain);
+            assert_eq!(chain.info().id, chain);
+        }
+
+        assert_eq!(ChainId::try_from(8453_u64).unwrap(), ChainId::Base);
+        assert!(ChainId::try_from(1287_u64).is_err());
+        assert!(ChainId::try_from(U256::MAX).is_err());
+    }
+
+    #[test]
+    fn chain_ids_serialize_as_numbers() {
+        assert_eq!(serde_json::to_string(&ChainId::Arbitrum).unwrap(), "42161");
+        assert_eq!(serde_json::from_str::<ChainId>("11155111").unwrap(), ChainId::Sepolia);
+        assert!(serde_json::from_str::<ChainId>("5").is_err());
+    }
+
+    #[test]
+    fn testnets_are_flagged() {
+        assert!(ChainId::Sepolia.is_testnet());
+        assert!(ChainId::AvalancheFuji.is_testnet());
+        assert!(!ChainId::ArbitrumNova.is_testnet());
+        assert!(ChainId::supported().all(|chain| !chain.info().zones.is_empty()));
+    }
+}
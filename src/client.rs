@@ -1,7 +1,10 @@
 use crate::*;
+#[cfg(feature = "server")]
+use jsonrpsee::core::RpcResult;
 use jsonrpsee::proc_macros::rpc;
 
-#[rpc(client)]
+#[cfg_attr(not(feature = "server"), rpc(client))]
+#[cfg_attr(feature = "server", rpc(server, client))]
 pub trait AoriBackendRpc {
     #[method(name = "aori_ping")]
     async fn ping(&self, parameters: AoriPingParams) -> RpcResult<String>;
@@ -22,7 +25,6 @@ pub trait AoriBackendRpc {
 
 #[cfg(test)]
 mod tests {
-    use super::*;
     use crate::{
         builder::AoriRequestBuilder, AoriBackendErrors, AoriBackendRpcClient, AoriPingParams,
     };
@@ -35,7 +37,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_ping() {
-        let _ = tracing_subscriber::fmt().with_max_level(Level::INFO).init();
+        let _ = tracing_subscriber::fmt().with_max_level(Level::INFO).try_init();
 
         let url = "https://v2.api.aori.io";
         let client = HttpClientBuilder::default().build(url).unwrap();
@@ -102,3 +104,58 @@ mod tests {
     #[tokio::test]
     async fn test_make_and_take() {}
 }
+
+#[cfg(all(test, feature = "server"))]
+mod server_tests {
+    use super::*;
+    use crate::test_utils::mock_server;
+    use jsonrpsee::{core::async_trait, http_client::HttpClientBuilder, types::ErrorObject};
+
+    /// Test double answering pings and refusing every cancellation
+    struct MockBackend;
+
+    #[async_trait]
+    impl AoriBackendRpcServer for MockBackend {
+        async fn ping(&self, _: AoriPingParams) -> RpcResult<String> {
+            Ok("aori_pong".to_string())
+        }
+
+        async fn request_quote(&self, _: AoriRequestQuoteParams) -> RpcResult<String> {
+            Ok(String::new())
+        }
+
+        async fn cancel_order(&self, _: AoriCancelOrderParams) -> RpcResult<String> {
+            Err(ErrorObject::owned(-32000, "Invalid API Key", None::<()>))
+        }
+
+        async fn cancel_all_orders(&self, _: AoriCancelAllOrdersParams) -> RpcResult<()> {
+            Ok(())
+        }
+
+        async fn make_order(&self, params: AoriMakeOrderParams) -> RpcResult<OrderView> {
+            Ok(to_order_view(params.order, params.signature, true, true))
+        }
+
+        async fn take_order(&self, _: AoriTakeOrderParams) -> RpcResult<String> {
+            Ok(String::new())
+        }
+
+        async fn view_orderbook(&self, _: ViewOrderbookQuery) -> RpcResult<Vec<OrderView>> {
+            Ok(vec![])
+        }
+    }
+
+    #[tokio::test]
+    async fn generated_server_answers_generated_client() {
+        let (url, handle) = mock_server(MockBackend.into_rpc()).await;
+
+        let client = HttpClientBuilder::default().build(&url).unwrap();
+        assert_eq!(client.ping(AoriPingParams::default()).await.unwrap(), "aori_pong");
+
+        let params = AoriCancelOrderParams { order_hash: "0x01".into(), api_key: "key".into() };
+        let error = AoriError::from_backend(client.cancel_order(params).await.unwrap_err());
+        assert!(matches!(error, AoriError::Backend(AoriBackendErrors::InvalidAPIKey())));
+
+        handle.stop().unwrap();
+    }
+}
@@ -0,0 +1,302 @@
+// deployment config
+// lets the SDK point at other deployments (testnets, local forks) without rebuilding
+
+use std::{env, fmt, fs, path::Path, str::FromStr};
+
+use alloy_primitives::Address;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    chains::ChainId,
+    constants::{
+        AORI_API_URL, AORI_PROVIDER_URL, DEFAULT_ZONE, SEATS_DAO_ADDRESS, SEATS_NFT_ADDRESS,
+    },
+    error::{AoriError, AoriResult},
+    zones::{ZoneRegistry, ZoneVersion},
+};
+
+pub const LOCAL_FORK_API_URL: &str = "http://127.0.0.1:8080";
+pub const LOCAL_FORK_PROVIDER_URL: &str = "http://127.0.0.1:8081";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Environment {
+    #[default]
+    Mainnet,
+    Testnet,
+    LocalFork,
+}
+
+impl FromStr for Environment {
+    type Err = AoriError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "mainnet" => Ok(Environment::Mainnet),
+            "testnet" => Ok(Environment::Testnet),
+            "local" | "local_fork" | "local-fork" => Ok(Environment::LocalFork),
+            other => Err(AoriError::Config(format!("Unknown environment `{}`", other))),
+        }
+    }
+}
+
+impl fmt::Display for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Environment::Mainnet => "mainnet",
+            Environment::Testnet => "testnet",
+            Environment::LocalFork => "local_fork",
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ZoneDeployment {
+    pub chain_id: u64,
+    pub zones: Vec<Address>,
+    #[serde(default)]
+    pub version: ZoneVersion,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeploymentConfig {
+    pub environment: Environment,
+    pub backend_url: String,
+    pub provider_url: String,
+    pub default_zone: Address,
+    pub seats_nft_address: Address,
+    pub seats_dao_address: Address,
+    /// Address the backend signs matchings with, checked before executing them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_signer: Option<Address>,
+    pub zones: Vec<ZoneDeployment>,
+}
+
+/// On-disk form of `DeploymentConfig`; anything left out falls back to the environment preset
+#[derive(Default, Deserialize)]
+struct DeploymentConfigFile {
+    environment: Option<Environment>,
+    backend_url: Option<String>,
+    provider_url: Option<String>,
+    default_zone: Option<Address>,
+    seats_nft_address: Option<Address>,
+    seats_dao_address: Option<Address>,
+    server_signer: Option<Address>,
+    zones: Option<Vec<ZoneDeployment>>,
+}
+
+fn builtin_zones(filter: impl Fn(ChainId) -> bool) -> Vec<ZoneDeployment> {
+    ChainId::supported()
+        .filter(|chain| filter(*chain))
+        .map(|chain| ZoneDeployment {
+            chain_id: chain.id(),
+            zones: chain.info().zones.to_vec(),
+            version: ZoneVersion::V2SingleChain,
+        })
+        .collect()
+}
+
+impl DeploymentConfig {
+    /// Production endpoints and zones on mainnets, as compiled into the SDK
+    pub fn mainnet() -> Self {
+        Self {
+            environment: Environment::Mainnet,
+            backend_url: AORI_API_URL.to_string(),
+            provider_url: AORI_PROVIDER_URL.to_string(),
+            default_zone: DEFAULT_ZONE.parse().unwrap_or_default(),
+            seats_nft_address: SEATS_NFT_ADDRESS.parse().unwrap_or_default(),
+            seats_dao_address: SEATS_DAO_ADDRESS.parse().unwrap_or_default(),
+            server_signer: None,
+            zones: builtin_zones(|chain| !chain.is_testnet()),
+        }
+    }
+
+    /// Production endpoints with zones on testnets only
+    pub fn testnet() -> Self {
+        Self {
+            environment: Environment::Testnet,
+            zones: builtin_zones(|chain| chain.is_testnet()),
+            ..Self::mainnet()
+        }
+    }
+
+    /// Local backend and provider, with every known zone since forks keep their addresses
+    pub fn local_fork() -> Self {
+        Self {
+            environment: Environment::LocalFork,
+            backend_url: LOCAL_FORK_API_URL.to_string(),
+            provider_url: LOCAL_FORK_PROVIDER_URL.to_string(),
+            zones: builtin_zones(|_| true),
+            ..Self::mainnet()
+        }
+    }
+
+    pub fn for_environment(environment: Environment) -> Self {
+        match environment {
+            Environment::Mainnet => Self::mainnet(),
+            Environment::Testnet => Self::testnet(),
+            Environment::LocalFork => Self::local_fork(),
+        }
+    }
+
+    pub fn from_toml_str(s: &str) -> AoriResult<Self> {
+        let file: DeploymentConfigFile =
+            toml::from_str(s).map_err(|e| AoriError::Config(e.to_string()))?;
+        Ok(Self::from_config_file(file))
+    }
+
+    pub fn from_json_str(s: &str) -> AoriResult<Self> {
+        let file: DeploymentConfigFile =
+            serde_json::from_str(s).map_err(|e| AoriError::Config(e.to_string()))?;
+        Ok(Self::from_config_file(file))
+    }
+
+    /// Reads a `.toml` or `.json` config file
+    pub fn from_file(path: impl AsRef<Path>) -> AoriResult<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .map_err(|e| AoriError::Config(format!("{}: {}", path.display(), e)))?;
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml_str(&contents),
+            Some("json") => Self::from_json_str(&contents),
+            _ => Err(AoriError::Config(format!(
+                "{}: expected a .toml or .json file",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Loads `.env`, then starts from `AORI_CONFIG` (a file) or `AORI_ENVIRONMENT` (a preset)
+    /// and applies the remaining `AORI_*` overrides on top
+    pub fn from_env() -> AoriResult<Self> {
+        dotenv::dotenv().ok();
+
+        let config = match env::var("AORI_CONFIG") {
+            Ok(path) => Self::from_file(path)?,
+            Err(_) => match env::var("AORI_ENVIRONMENT") {
+                Ok(environment) => Self::for_environment(environment.parse()?),
+                Err(_) => Self::mainnet(),
+            },
+        };
+
+        config.with_env_overrides()
+    }
+
+    /// Overrides fields from `AORI_BACKEND_URL`, `AORI_PROVIDER_URL`, `AORI_DEFAULT_ZONE`,
+    /// `AORI_SEATS_NFT_ADDRESS`, `AORI_SEATS_DAO_ADDRESS` and `AORI_SERVER_SIGNER` when they
+    /// are set
+    pub fn with_env_overrides(mut self) -> AoriResult<Self> {
+        if let Ok(url) = env::var("AORI_BACKEND_URL") {
+            self.backend_url = url;
+        }
+        if let Ok(url) = env::var("AORI_PROVIDER_URL") {
+            self.provider_url = url;
+        }
+        if let Some(zone) = address_from_env("AORI_DEFAULT_ZONE")? {
+            self.default_zone = zone;
+        }
+        if let Some(address) = address_from_env("AORI_SEATS_NFT_ADDRESS")? {
+            self.seats_nft_address = address;
+        }
+        if let Some(address) = address_from_env("AORI_SEATS_DAO_ADDRESS")? {
+            self.seats_dao_address = address;
+        }
+        if let Some(address) = address_from_env("AORI_SERVER_SIGNER")? {
+            self.server_signer = Some(address);
+        }
+        Ok(self)
+    }
+
+    pub fn zones(&self, chain_id: u64) -> &[Address] {
+        self.zones
+            .iter()
+            .find(|deployment| deployment.chain_id == chain_id)
+            .map(|deployment| deployment.zones.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn is_zone_supported(&self, chain_id: u64, zone: Address) -> bool {
+        self.zones(chain_id).contains(&zone)
+    }
+
+    /// Zones of this deployment, to validate orders against with `validate_order`
+    pub fn zone_registry(&self) -> AoriResult<ZoneRegistry> {
+        ZoneRegistry::from_config(self)
+    }
+
+    fn from_config_file(file: DeploymentConfigFile) -> Self {
+        let preset = Self::for_environment(file.environment.unwrap_or_default());
+        Self {
+            environment: preset.environment,
+            backend_url: file.backend_url.unwrap_or(preset.backend_url),
+            provider_url: file.provider_url.unwrap_or(preset.provider_url),
+            default_zone: file.default_zone.unwrap_or(preset.default_zone),
+            seats_nft_address: file.seats_nft_address.unwrap_or(preset.seats_nft_address),
+            seats_dao_address: file.seats_dao_address.unwrap_or(preset.seats_dao_address),
+            server_signer: file.server_signer.or(preset.server_signer),
+            zones: file.zones.unwrap_or(preset.zones),
+        }
+    }
+}
+
+impl Default for DeploymentConfig {
+    fn default() -> Self {
+        Self::mainnet()
+    }
+}
+
+fn address_from_env(key: &str) -> AoriResult<Option<Address>> {
+    match env::var(key) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|e| AoriError::Config(format!("{}: invalid address `{}`: {}", key, value, e))),
+        Err(_) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_use_builtin_constants() {
+        let mainnet = DeploymentConfig::mainnet();
+        assert_eq!(mainnet.backend_url, AORI_API_URL);
+        assert_eq!(mainnet.default_zone.to_checksum(None), DEFAULT_ZONE);
+        assert_eq!(mainnet.seats_nft_address.to_checksum(None), SEATS_NFT_ADDRESS);
+        assert!(!mainnet.zones(ChainId::Arbitrum.id()).is_empty());
+        assert!(mainnet.zones(ChainId::Sepolia.id()).is_empty());
+
+        let testnet = DeploymentConfig::testnet();
+        assert!(!testnet.zones(ChainId::Sepolia.id()).is_empty());
+        assert!(testnet.zones(ChainId::Arbitrum.id()).is_empty());
+    }
+
+    #[test]
+    fn file_fields_override_environment_preset() {
+        let config = DeploymentConfig::from_toml_str(
+            r#"
+            environment = "local_fork"
+            provider_url = "http://127.0.0.1:9000"
+
+            [[zones]]
+            chain_id = 42161
+            zones = ["0x0000000000000000000000000000000000000abc"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.environment, Environment::LocalFork);
+        assert_eq!(config.backend_url, LOCAL_FORK_API_URL);
+        assert_eq!(config.provider_url, "http://127.0.0.1:9000");
+        let zone = "0x0000000000000000000000000000000000000abc".parse().unwrap();
+        assert!(config.is_zone_supported(42161, zone));
+        assert!(!config.is_zone_supported(42161, config.default_zone));
+        assert!(config.zones(ChainId::Base.id()).is_empty());
+
+        let json = DeploymentConfig::from_json_str(r#"{ "environment": "testnet" }"#).unwrap();
+        assert_eq!(json, DeploymentConfig::testnet());
+    }
+}
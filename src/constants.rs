@@ -1,70 +1,35 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::OnceLock,
+};
 
-use alloy_primitives::U256;
+use alloy_primitives::{Address, U256};
 
-#[derive(PartialEq, Eq, Hash, Copy, Clone)]
-pub enum ChainId {
-    ////////////////////////////////////////////////////////////////
-    //                          MAINNETS
-    ////////////////////////////////////////////////////////////////
-    Ethereum = 1,
-    Optimism = 10,
-    Bsc = 56,
-    Gnosis = 100,
-    Polygon = 137,
-    Fantom = 250,
-    Arbitrum = 42161,
-    Avalanche = 43114,
-    Blast = 81457,
-    Base = 1287,
-    Sepolia = 11155111,
-    ArbitrumNova = 421611,
-    AvalancheFuji = 43113,
+pub use crate::chains::ChainId;
+use crate::zones::{ZoneRegistry, ZoneVersion};
 
-    ////////////////////////////////////////////////////////////////
-    //                          TESTNETS
-    ////////////////////////////////////////////////////////////////
-    BerachainArtio = 80085,
+#[allow(non_snake_case)]
+pub fn AORI_V2_SINGLE_CHAIN_ZONE_ADDRESSES() -> &'static HashMap<ChainId, HashSet<Address>> {
+    static ZONES: OnceLock<HashMap<ChainId, HashSet<Address>>> = OnceLock::new();
+    ZONES.get_or_init(|| {
+        let registry = ZoneRegistry::builtin();
+        registry
+            .chains()
+            .map(|chain| {
+                (chain, registry.zones_with_version(chain, ZoneVersion::V2SingleChain).collect())
+            })
+            .collect()
+    })
 }
 
-pub fn AORI_V2_SINGLE_CHAIN_ZONE_ADDRESSES() -> HashMap<ChainId, HashSet<String>> {
-    HashMap::from([
-        (
-            ChainId::Optimism,
-            HashSet::from(["0xcc1A0DA89593441571f35Dd99a0aC1856d3F1FB5".to_string()]),
-        ),
-        (
-            ChainId::Polygon,
-            HashSet::from(["0xcc1A0DA89593441571f35Dd99a0aC1856d3F1FB5".to_string()]),
-        ),
-        (ChainId::Blast, HashSet::from(["0xcc1A0DA89593441571f35Dd99a0aC1856d3F1FB5".to_string()])),
-        (ChainId::Base, HashSet::from(["0xcc1A0DA89593441571f35Dd99a0aC1856d3F1FB5".to_string()])),
-        (
-            ChainId::Arbitrum,
-            HashSet::from([
-                "0xcc1A0DA89593441571f35Dd99a0aC1856d3F1FB5".to_string(),
-                "0x6A979916234013AbA003d906e4e7136496B90AA6".to_string(),
-            ]),
-        ),
-        (
-            ChainId::Sepolia,
-            HashSet::from([
-                "0xcc1A0DA89593441571f35Dd99a0aC1856d3F1FB5".to_string(),
-                "0x6A979916234013AbA003d906e4e7136496B90AA6".to_string(),
-            ]),
-        ),
-        (
-            ChainId::BerachainArtio,
-            HashSet::from(["0xcc1A0DA89593441571f35Dd99a0aC1856d3F1FB5".to_string()]),
-        ),
-    ])
+#[allow(non_snake_case)]
+pub fn SUPPORTED_AORI_CHAINS() -> &'static HashSet<U256> {
+    static CHAINS: OnceLock<HashSet<U256>> = OnceLock::new();
+    CHAINS.get_or_init(|| ChainId::supported().map(|chain| U256::from(chain.id())).collect())
 }
 
-pub fn SUPPORTED_AORI_CHAINS() -> HashSet<U256> {
-    HashSet::from_iter(
-        AORI_V2_SINGLE_CHAIN_ZONE_ADDRESSES().keys().into_iter().map(|x| U256::from(*x as u64)),
-    )
-}
+pub const AORI_API_URL: &str = "https://v2.api.aori.io";
+pub const AORI_PROVIDER_URL: &str = "https://provider.aori.io";
 
 pub const DEFAULT_ZONE: &str = "0xeA2b4e7F02b859305093f9F4778a19D66CA176d5";
 pub const DEFAULT_ZONEHASH: &str =
@@ -0,0 +1,346 @@
+// AoriV2 contract
+// builds calls against the zone contract and submits them through the data provider
+
+use std::str::FromStr;
+
+use alloy_primitives::{Address, Bytes, B256, U256};
+use alloy_sol_types::{SolCall, SolInterface, SolValue};
+use ethers::{
+    signers::{LocalWallet, Signer},
+    types::{transaction::eip2718::TypedTransaction, TransactionRequest},
+};
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{
+    error::{AoriError, AoriResult, ContractError},
+    provider::ProviderClient,
+    AoriMatchingDetails,
+    AoriV2::{self, AoriV2Errors},
+};
+
+/// A call ready to be signed, simulated or handed to an `eth_call`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContractCall {
+    pub to: Address,
+    pub chain_id: u64,
+    pub data: Bytes,
+    pub value: U256,
+}
+
+/// Arguments of a `settleOrders` call
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettleOrdersCall {
+    pub matching: AoriMatchingDetails,
+    pub server_signature: Bytes,
+    pub hook_data: Bytes,
+    pub options: Bytes,
+}
+
+/// Decodes `settleOrders` calldata, selector included
+pub fn decode_settle_orders(data: &[u8]) -> AoriResult<SettleOrdersCall> {
+    let call = AoriV2::settleOrdersCall::abi_decode(data, true)
+        .map_err(|e| AoriError::Encoding(e.to_string()))?;
+    // the ABI's `IAoriV2.MatchingDetails` has the same layout as `AoriMatchingDetails`
+    let matching = AoriMatchingDetails::abi_decode(&call.matching.abi_encode(), true)
+        .map_err(|e| AoriError::Encoding(e.to_string()))?;
+
+    Ok(SettleOrdersCall {
+        matching,
+        server_signature: call.serverSignature,
+        hook_data: call.hookData,
+        options: call.options,
+    })
+}
+
+impl ContractError {
+    /// Decodes revert data from the zone contract. Returns `None` for data that isn't one of
+    /// the contract's custom errors
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let error = AoriV2Errors::abi_decode(data, true).ok()?;
+        Some(match error {
+            AoriV2Errors::SafeERC20FailedOperation(e) => {
+                ContractError::SafeERC20FailedOperation(e.token)
+            }
+            AoriV2Errors::AddressInsufficientBalance(e) => {
+                ContractError::AddressInsufficientBalance(e.account)
+            }
+            AoriV2Errors::AddressEmptyCode(e) => ContractError::AddressEmptyCode(e.target),
+            AoriV2Errors::FailedInnerCall(_) => ContractError::FailedInnerCall(),
+        })
+    }
+
+    /// Finds revert data in a JSON-RPC error's `data` field, either as a hex string or
+    /// nested under `data`
+    pub fn from_error_data(data: &Value) -> Option<Self> {
+        match data {
+            Value::String(s) => Self::decode(&hex::decode(s.trim_start_matches("0x")).ok()?),
+            Value::Object(map) => map.get("data").and_then(Self::from_error_data),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AoriV2Contract {
+    pub address: Address,
+    pub chain_id: u64,
+    provider: ProviderClient,
+}
+
+impl AoriV2Contract {
+    pub fn new(address: Address, chain_id: u64, provider: ProviderClient) -> Self {
+        Self { address, chain_id, provider }
+    }
+
+    pub fn provider(&self) -> &ProviderClient {
+        &self.provider
+    }
+
+    fn call(&self, call: impl SolCall) -> ContractCall {
+        ContractCall {
+            to: self.address,
+            chain_id: self.chain_id,
+            data: call.abi_encode().into(),
+            value: U256::ZERO,
+        }
+    }
+
+    ////////////////////////////////////////////////////////////////
+    //                          CALLDATA
+    ////////////////////////////////////////////////////////////////
+
+    pub fn deposit(&self, account: Address, token: Address, amount: U256) -> ContractCall {
+        self.call(AoriV2::depositCall { _account: account, _token: token, _amount: amount })
+    }
+
+    pub fn withdraw(&self, token: Address, amount: U256) -> ContractCall {
+        self.call(AoriV2::withdrawCall { _token: token, _amount: amount })
+    }
+
+    pub fn balance_of(&self, account: Address, token: Address) -> ContractCall {
+        self.call(AoriV2::balanceOfCall { _account: account, _token: token })
+    }
+
+    /// Counter of the calling address
+    pub fn get_counter(&self) -> ContractCall {
+        self.call(AoriV2::getCounterCall {})
+    }
+
+    /// Invalidates every order signed with the caller's current counter
+    pub fn increment_counter(&self) -> ContractCall {
+        self.call(AoriV2::incrementCounterCall {})
+    }
+
+    pub fn flash_loan(
+        &self,
+        recipient: Address,
+        token: Address,
+        amount: U256,
+        user_data: Bytes,
+        receive_token: bool,
+    ) -> ContractCall {
+        self.call(AoriV2::flashLoanCall {
+            recipient,
+            token,
+            amount,
+            userData: user_data,
+            receiveToken: receive_token,
+        })
+    }
+
+    pub fn has_order_settled(&self, order_hash: B256) -> ContractCall {
+        self.call(AoriV2::hasOrderSettledCall { orderHash: order_hash })
+    }
+
+    pub fn set_taker_fee(&self, taker_fee_bips: u8, taker_fee_address: Address) -> ContractCall {
+        self.call(AoriV2::setTakerFeeCall {
+            _takerFeeBips: taker_fee_bips,
+            _takerFeeAddress: taker_fee_address,
+        })
+    }
+
+    ////////////////////////////////////////////////////////////////
+    //                        RETURN VALUES
+    ////////////////////////////////////////////////////////////////
+
+    pub fn decode_balance_of(data: &[u8]) -> AoriResult<U256> {
+        decode_return::<AoriV2::balanceOfCall>(data).map(|r| r.balance)
+    }
+
+    pub fn decode_get_counter(data: &[u8]) -> AoriResult<U256> {
+        decode_return::<AoriV2::getCounterCall>(data).map(|r| r._0)
+    }
+
+    pub fn decode_has_order_settled(data: &[u8]) -> AoriResult<bool> {
+        decode_return::<AoriV2::hasOrderSettledCall>(data).map(|r| r.settled)
+    }
+
+    ////////////////////////////////////////////////////////////////
+    //                         SUBMISSION
+    ////////////////////////////////////////////////////////////////
+
+    /// Signs `call` as a legacy transaction from `wallet`, filling nonce, gas and gas price
+    /// from the data provider, and returns the raw signed transaction
+    pub async fn sign(&self, call: &ContractCall, wallet: &LocalWallet) -> AoriResult<Bytes> {
+        let from = wallet.address();
+        let nonce = self.provider.get_nonce(format!("{:?}", from), call.chain_id).await?;
+        let gas = self
+            .provider
+            .estimate_gas(
+                format!("{:?}", from),
+                call.to.to_string(),
+                call.value.to_string(),
+                call.data.to_string(),
+                call.chain_id,
+            )
+            .await?;
+        let fee_data = self.provider.get_fee_data(call.chain_id).await?;
+
+        let tx: TypedTransaction = TransactionRequest::new()
+            .from(from)
+            .to(ethers::types::Address::from(call.to.0 .0))
+            .data(call.data.to_vec())
+            .value(to_ethers_u256(call.value))
+            .nonce(to_ethers_u256(parse_quantity(&nonce.result)?))
+            .gas(to_ethers_u256(parse_quantity(&gas.result)?))
+            .gas_price(to_ethers_u256(parse_gas_price(&fee_data.result)?))
+            .chain_id(call.chain_id)
+            .into();
+
+        let signature = wallet.sign_transaction_sync(&tx)?;
+        Ok(tx.rlp_signed(&signature).0.into())
+    }
+
+    /// Signs and submits `call` through `ProviderClient::send_transaction`, returning the
+    /// transaction hash
+    pub async fn send(&self, call: &ContractCall, wallet: &LocalWallet) -> AoriResult<B256> {
+        let signed_tx = self.sign(call, wallet).await?;
+        let response = self.provider.send_transaction(signed_tx.to_string()).await?;
+        B256::from_str(&response.result).map_err(|e| AoriError::Encoding(e.to_string()))
+    }
+}
+
+fn decode_return<C: SolCall>(data: &[u8]) -> AoriResult<C::Return> {
+    C::abi_decode_returns(data, true).map_err(|e| match ContractError::decode(data) {
+        Some(revert) => AoriError::Contract(revert),
+        None => AoriError::Encoding(e.to_string()),
+    })
+}
+
+fn to_ethers_u256(value: U256) -> ethers::types::U256 {
+    ethers::types::U256::from_big_endian(&value.to_be_bytes::<32>())
+}
+
+/// Parses a decimal or `0x` hex quantity as returned by the data provider
+pub(crate) fn parse_quantity(value: &str) -> AoriResult<U256> {
+    U256::from_str(value.trim().trim_matches('"'))
+        .map_err(|e| AoriError::Encoding(format!("invalid quantity `{}`: {}", value, e)))
+}
+
+/// Fee data comes back either as a bare gas price or as an object of fee fields
+fn parse_gas_price(fee_data: &str) -> AoriResult<U256> {
+    if let Ok(price) = parse_quantity(fee_data) {
+        return Ok(price);
+    }
+
+    let fields: Value = serde_json::from_str(fee_data)?;
+    ["gasPrice", "maxFeePerGas"]
+        .iter()
+        .find_map(|key| match &fields[key] {
+            Value::String(s) => parse_quantity(s).ok(),
+            Value::Number(n) => n.as_u64().map(U256::from),
+            _ => None,
+        })
+        .ok_or_else(|| AoriError::Encoding(format!("no gas price in fee data `{}`", fee_data)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AoriOrder;
+    use alloy_sol_types::SolError;
+
+    fn contract() -> AoriV2Contract {
+        AoriV2Contract::new(Address::repeat_byte(3), 42161, ProviderClient::new(vec![]))
+    }
+
+    #[test]
+    fn encodes_calls_with_selectors() {
+        let contract = contract();
+        let deposit = contract.deposit(Address::repeat_byte(1), Address::repeat_byte(2), U256::MAX);
+        assert_eq!(deposit.data[..4], AoriV2::depositCall::SELECTOR);
+        assert_eq!(deposit.data.len(), 4 + 32 * 3);
+        assert_eq!(deposit.to, contract.address);
+
+        let decoded = AoriV2::depositCall::abi_decode(&deposit.data, true).unwrap();
+        assert_eq!(decoded._amount, U256::MAX);
+
+        assert_eq!(contract.increment_counter().data[..], AoriV2::incrementCounterCall::SELECTOR);
+        assert_eq!(contract.set_taker_fee(5, Address::ZERO).data.len(), 4 + 32 * 2);
+    }
+
+    #[test]
+    fn decodes_settle_orders_calldata() {
+        let matching = AoriMatchingDetails {
+            makerOrder: AoriOrder { inputAmount: U256::from(1000), ..Default::default() },
+            takerOrder: AoriOrder { outputAmount: U256::from(1000), ..Default::default() },
+            makerSignature: Bytes::from(vec![1; 65]),
+            takerSignature: Bytes::from(vec![2; 65]),
+            blockDeadline: U256::from(10467500),
+            seatNumber: U256::from(3),
+            seatHolder: Address::repeat_byte(9),
+            seatPercentOfFees: U256::from(50),
+        };
+        let arguments =
+            (matching.clone(), Bytes::from(vec![3; 65]), Bytes::new(), Bytes::from(vec![4]));
+        let data =
+            [&AoriV2::settleOrdersCall::SELECTOR[..], &arguments.abi_encode_params()].concat();
+
+        let call = decode_settle_orders(&data).unwrap();
+        assert_eq!(call.matching.abi_encode(), matching.abi_encode());
+        assert_eq!(call.server_signature, Bytes::from(vec![3; 65]));
+        assert_eq!(call.options, Bytes::from(vec![4]));
+        assert!(decode_settle_orders(&data[..100]).is_err());
+    }
+
+    #[test]
+    fn decodes_returns_and_custom_errors() {
+        let counter = U256::from(7).to_be_bytes::<32>();
+        assert_eq!(AoriV2Contract::decode_get_counter(&counter).unwrap(), U256::from(7));
+        assert!(
+            AoriV2Contract::decode_has_order_settled(&U256::from(1).to_be_bytes::<32>()).unwrap()
+        );
+
+        let revert =
+            AoriV2::SafeERC20FailedOperation { token: Address::repeat_byte(2) }.abi_encode();
+        assert_eq!(
+            ContractError::decode(&revert),
+            Some(ContractError::SafeERC20FailedOperation(Address::repeat_byte(2)))
+        );
+        assert!(matches!(
+            AoriV2Contract::decode_balance_of(&revert),
+            Err(AoriError::Contract(ContractError::SafeERC20FailedOperation(_)))
+        ));
+
+        let data = serde_json::json!(Bytes::from(
+            AoriV2::AddressInsufficientBalance { account: Address::repeat_byte(1) }.abi_encode()
+        ));
+        assert_eq!(
+            ContractError::from_error_data(&data),
+            Some(ContractError::AddressInsufficientBalance(Address::repeat_byte(1)))
+        );
+    }
+
+    #[test]
+    fn parses_provider_fee_data() {
+        assert_eq!(parse_gas_price("0x3b9aca00").unwrap(), U256::from(1_000_000_000));
+        assert_eq!(parse_gas_price("100").unwrap(), U256::from(100));
+        assert_eq!(
+            parse_gas_price(r#"{"gasPrice":"0x64","maxFeePerGas":null}"#).unwrap(),
+            U256::from(100)
+        );
+        assert!(parse_gas_price("{}").is_err());
+    }
+}
@@ -1,6 +1,182 @@
-use jsonrpsee::{core::ClientError, types::ErrorObject};
+use alloy_primitives::{Address, U256};
+use jsonrpsee::{
+    core::ClientError, http_client::transport::Error as TransportError, types::ErrorObject,
+};
 use thiserror::Error;
-use tracing::error;
+
+pub type AoriResult<T> = Result<T, AoriError>;
+
+/// Top-level error returned by every public SDK API
+#[derive(Error, Debug)]
+pub enum AoriError {
+    #[error("Backend error: {0}")]
+    Backend(#[from] AoriBackendErrors),
+    #[error("Provider error: {0}")]
+    Provider(String),
+    #[error("Signing error: {0}")]
+    Signing(String),
+    #[error("Validation error: {0}")]
+    Validation(#[from] ValidationError),
+    #[error("Encoding error: {0}")]
+    Encoding(String),
+    #[error("Transport error: {0}")]
+    Transport(String),
+    #[error("Config error: {0}")]
+    Config(String),
+    #[error("Contract error: {0}")]
+    Contract(ContractError),
+    #[error("Timed out: {0}")]
+    Timeout(String),
+    #[error("Storage error: {0}")]
+    Storage(String),
+    #[error("Rate limited: {0}")]
+    RateLimited(String),
+}
+
+impl AoriError {
+    /// Maps a JSON-RPC client error from the Aori backend, keeping call errors as
+    /// `AoriBackendErrors` and everything else as a transport failure
+    pub fn from_backend(e: ClientError) -> Self {
+        match e {
+            ClientError::Call(err) => AoriError::Backend(err.into()),
+            e => AoriError::from_transport(e),
+        }
+    }
+
+    /// Maps a JSON-RPC client error from the Aori data provider
+    pub fn from_provider(e: ClientError) -> Self {
+        match e {
+            ClientError::Call(err) => {
+                let revert = err
+                    .data()
+                    .and_then(|data| serde_json::from_str(data.get()).ok())
+                    .and_then(|data| ContractError::from_error_data(&data));
+                match revert {
+                    Some(revert) => AoriError::Contract(revert),
+                    None => AoriError::Provider(err.message().to_owned()),
+                }
+            }
+            e => AoriError::from_transport(e),
+        }
+    }
+
+    /// Transport failures, except timeouts and requests dropped by the client's `RateLimiter`
+    fn from_transport(e: ClientError) -> Self {
+        let sdk = match &e {
+            ClientError::RequestTimeout => Some(AoriError::Timeout(e.to_string())),
+            ClientError::Transport(transport) => {
+                transport.downcast_ref::<TransportError>().and_then(sdk_error)
+            }
+            _ => None,
+        };
+        sdk.unwrap_or_else(|| AoriError::Transport(e.to_string()))
+    }
+}
+
+/// Errors raised by the SDK's own HTTP middleware, which reach jsonrpsee as
+/// `TransportError::Http`
+pub(crate) fn sdk_error(e: &TransportError) -> Option<AoriError> {
+    let TransportError::Http(e) = e else { return None };
+    match e.downcast_ref::<AoriError>()? {
+        AoriError::RateLimited(message) => Some(AoriError::RateLimited(message.clone())),
+        AoriError::Timeout(message) => Some(AoriError::Timeout(message.clone())),
+        _ => None,
+    }
+}
+
+impl From<serde_json::Error> for AoriError {
+    fn from(e: serde_json::Error) -> Self {
+        AoriError::Encoding(e.to_string())
+    }
+}
+
+impl From<bson::ser::Error> for AoriError {
+    fn from(e: bson::ser::Error) -> Self {
+        AoriError::Encoding(e.to_string())
+    }
+}
+
+impl From<bson::de::Error> for AoriError {
+    fn from(e: bson::de::Error) -> Self {
+        AoriError::Encoding(e.to_string())
+    }
+}
+
+impl From<mongodb::error::Error> for AoriError {
+    fn from(e: mongodb::error::Error) -> Self {
+        AoriError::Storage(e.to_string())
+    }
+}
+
+impl From<hex::FromHexError> for AoriError {
+    fn from(e: hex::FromHexError) -> Self {
+        AoriError::Encoding(e.to_string())
+    }
+}
+
+impl From<ethers::signers::WalletError> for AoriError {
+    fn from(e: ethers::signers::WalletError) -> Self {
+        AoriError::Signing(e.to_string())
+    }
+}
+
+impl From<ethers::types::SignatureError> for AoriError {
+    fn from(e: ethers::types::SignatureError) -> Self {
+        AoriError::Signing(e.to_string())
+    }
+}
+
+/// Custom errors the AoriV2 contract reverts with
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ContractError {
+    #[error("SafeERC20 operation on token {0} failed")]
+    SafeERC20FailedOperation(Address),
+    #[error("Account {0} has insufficient balance")]
+    AddressInsufficientBalance(Address),
+    #[error("Address {0} has no code")]
+    AddressEmptyCode(Address),
+    #[error("Inner call failed")]
+    FailedInnerCall(),
+}
+
+/// Reasons an order or request is rejected locally before reaching the backend
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    #[error("Input chain not supported")]
+    InputChainNotSupported(),
+    #[error("Output chain not supported")]
+    OutputChainNotSupported(),
+    #[error("No signature provided")]
+    NoSignatureProvided(),
+    #[error("Input token and output token must be different if they are on the same chain")]
+    InputAndOutputTokensAreTheSame(),
+    #[error("Input amount cannot be zero")]
+    InputAmountIsZero(),
+    #[error("Output amount cannot be zero")]
+    OutputAmountIsZero(),
+    #[error("Start time cannot be after end time")]
+    StartTimeAfterEndTime(),
+    #[error("End time cannot be in the past")]
+    EndTimeInThePast(),
+    #[error("Signature signer could not be retrieved")]
+    SignerNotRecoverable(),
+    #[error("Missing input amount")]
+    MissingInputAmount(),
+    #[error("Chain {0} is not supported")]
+    UnsupportedChainId(U256),
+    #[error("Zone {0} on chain {1} is not supported")]
+    ZoneNotSupported(Address, U256),
+    #[error("Matching hash does not match the matching details")]
+    MatchingHashMismatch(),
+    #[error("Matching was signed by {0} instead of the server signer")]
+    ServerSignerMismatch(Address),
+    #[error("Maker order hash does not match the maker order")]
+    MakerOrderHashMismatch(),
+    #[error("Taker order hash does not match the taker order")]
+    TakerOrderHashMismatch(),
+    #[error("{0} does not match the matching details")]
+    DetailsFieldMismatch(&'static str),
+}
 
 // shoutout chatgpt ^^
 
@@ -147,7 +323,7 @@ pub enum AoriBackendErrors {
 }
 
 impl AoriBackendErrors {
-    fn from_error_message(message: &str) -> Self {
+    pub fn from_error_message(message: &str) -> Self {
         match message {
             "Your JSON-RPC payload data doesn't fit into the correct format" => AoriBackendErrors::IncorrectRequestFormat(),
             "Invalid Authorization" => AoriBackendErrors::InvalidAuthorization(),
@@ -0,0 +1,298 @@
+// feed filtering and routing
+// narrows the `AoriFeedEvents` stream down and hands events to per-variant handlers
+
+use std::{collections::HashMap, future::Future, sync::Arc};
+
+use alloy_primitives::{Address, U256};
+use futures::{future::BoxFuture, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use super::{subscription::SubscriptionEvents, AoriFeedEvents};
+
+/// Predicate over feed events. Field predicates reject events that don't carry that field,
+/// e.g. `Maker` never matches an `OrderCreated`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "camelCase")]
+pub enum FeedFilter {
+    #[default]
+    Any,
+    Event(SubscriptionEvents),
+    ChainId(u64),
+    InputToken(Address),
+    OutputToken(Address),
+    Offerer(Address),
+    Maker(Address),
+    Taker(Address),
+    MinInputAmount(U256),
+    MinOutputAmount(U256),
+    All(Vec<FeedFilter>),
+    AnyOf(Vec<FeedFilter>),
+    Not(Box<FeedFilter>),
+}
+
+impl FeedFilter {
+    /// Matches the pair regardless of which side is the input token
+    pub fn pair(base: Address, quote: Address) -> Self {
+        FeedFilter::AnyOf(vec![
+            FeedFilter::InputToken(base).and(FeedFilter::OutputToken(quote)),
+            FeedFilter::InputToken(quote).and(FeedFilter::OutputToken(base)),
+        ])
+    }
+
+    pub fn events(events: impl IntoIterator<Item = SubscriptionEvents>) -> Self {
+        FeedFilter::AnyOf(events.into_iter().map(FeedFilter::Event).collect())
+    }
+
+    pub fn chain_ids(chain_ids: impl IntoIterator<Item = u64>) -> Self {
+        FeedFilter::AnyOf(chain_ids.into_iter().map(FeedFilter::ChainId).collect())
+    }
+
+    pub fn and(self, other: FeedFilter) -> Self {
+        match (self, other) {
+            (FeedFilter::Any, other) | (other, FeedFilter::Any) => other,
+            (FeedFilter::All(mut filters), FeedFilter::All(others)) => {
+                filters.extend(others);
+                FeedFilter::All(filters)
+            }
+            (FeedFilter::All(mut filters), other) => {
+                filters.push(other);
+                FeedFilter::All(filters)
+            }
+            (filter, other) => FeedFilter::All(vec![filter, other]),
+        }
+    }
+
+    pub fn or(self, other: FeedFilter) -> Self {
+        match (self, other) {
+            (FeedFilter::AnyOf(mut filters), FeedFilter::AnyOf(others)) => {
+                filters.extend(others);
+                FeedFilter::AnyOf(filters)
+            }
+            (FeedFilter::AnyOf(mut filters), other) => {
+                filters.push(other);
+                FeedFilter::AnyOf(filters)
+            }
+            (filter, other) => FeedFilter::AnyOf(vec![filter, other]),
+        }
+    }
+
+    pub fn negate(self) -> Self {
+        FeedFilter::Not(Box::new(self))
+    }
+
+    pub fn matches(&self, event: &AoriFeedEvents) -> bool {
+        match self {
+            FeedFilter::Any => true,
+            FeedFilter::Event(kind) => SubscriptionEvents::from(event) == *kind,
+            FeedFilter::ChainId(chain_id) => event.chain_id() == *chain_id,
+            FeedFilter::InputToken(token) => {
+                event.tokens().is_some_and(|(input, _)| input == *token)
+            }
+            FeedFilter::OutputToken(token) => {
+                event.tokens().is_some_and(|(_, output)| output == *token)
+            }
+            FeedFilter::Offerer(address) => event.offerer() == Some(*address),
+            FeedFilter::Maker(address) => event.maker() == Some(*address),
+            FeedFilter::Taker(address) => event.taker() == Some(*address),
+            FeedFilter::MinInputAmount(amount) => {
+                event.input_amount().is_some_and(|a| a >= *amount)
+            }
+            FeedFilter::MinOutputAmount(amount) => {
+                event.output_amount().is_some_and(|a| a >= *amount)
+            }
+            FeedFilter::All(filters) => filters.iter().all(|filter| filter.matches(event)),
+            FeedFilter::AnyOf(filters) => filters.iter().any(|filter| filter.matches(event)),
+            FeedFilter::Not(filter) => !filter.matches(event),
+        }
+    }
+}
+
+/// Drops every event from `feed` that `filter` doesn't match
+pub fn filter_feed<S>(feed: S, filter: FeedFilter) -> impl Stream<Item = AoriFeedEvents>
+where
+    S: Stream<Item = AoriFeedEvents>,
+{
+    feed.filter(move |event| futures::future::ready(filter.matches(event)))
+}
+
+pub type FeedHandler = Arc<dyn Fn(AoriFeedEvents) -> BoxFuture<'static, ()> + Send + Sync>;
+
+struct Route {
+    filter: FeedFilter,
+    handler: FeedHandler,
+}
+
+/// Dispatches feed events to async handlers registered per event variant
+#[derive(Default)]
+pub struct FeedRouter {
+    filter: FeedFilter,
+    routes: HashMap<SubscriptionEvents, Vec<Route>>,
+}
+
+impl FeedRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Filter applied to every event before any route is considered
+    pub fn with_filter(mut self, filter: FeedFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn on<F, Fut>(self, event: SubscriptionEvents, handler: F) -> Self
+    where
+        F: Fn(AoriFeedEvents) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_filtered(event, FeedFilter::Any, handler)
+    }
+
+    pub fn on_filtered<F, Fut>(
+        mut self,
+        event: SubscriptionEvents,
+        filter: FeedFilter,
+        handler: F,
+    ) -> Self
+    where
+        F: Fn(AoriFeedEvents) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let handler: FeedHandler = Arc::new(move |event| Box::pin(handler(event)));
+        self.routes.entry(event).or_default().push(Route { filter, handler });
+        self
+    }
+
+    /// Runs every matching handler for `event` concurrently, returning how many ran
+    pub async fn dispatch(&self, event: AoriFeedEvents) -> usize {
+        if !self.filter.matches(&event) {
+            return 0;
+        }
+
+        let Some(routes) = self.routes.get(&SubscriptionEvents::from(&event)) else {
+            return 0;
+        };
+
+        let handlers: Vec<_> = routes
+            .iter()
+            .filter(|route| route.filter.matches(&event))
+            .map(|route| (route.handler)(event.clone()))
+            .collect();
+        let count = handlers.len();
+        futures::future::join_all(handlers).await;
+        count
+    }
+
+    /// Dispatches events from `feed` one at a time until it ends
+    pub async fn run<S>(&self, feed: S)
+    where
+        S: Stream<Item = AoriFeedEvents>,
+    {
+        let mut feed = std::pin::pin!(feed);
+        while let Some(event) = feed.next().await {
+            self.dispatch(event).await;
+        }
+    }
+
+    /// Like `run`, but dispatches up to `concurrency` events at once
+    pub async fn run_concurrent<S>(&self, feed: S, concurrency: usize)
+    where
+        S: Stream<Item = AoriFeedEvents>,
+    {
+        feed.for_each_concurrent(concurrency, |event| async move {
+            self.dispatch(event).await;
+        })
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{to_order_view, AoriOrder, QuoteRequestedData};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn order_created(input_amount: u64) -> AoriFeedEvents {
+        let order = AoriOrder {
+            offerer: Address::repeat_byte(1),
+            inputToken: Address::repeat_byte(2),
+            inputAmount: U256::from(input_amount),
+            inputChainId: U256::from(42161),
+            outputToken: Address::repeat_byte(4),
+            outputAmount: U256::from(2000),
+            outputChainId: U256::from(42161),
+            ..Default::default()
+        };
+        AoriFeedEvents::OrderCreated(Box::new(to_order_view(order, "0x".to_string(), true, true)))
+    }
+
+    fn quote_requested() -> AoriFeedEvents {
+        AoriFeedEvents::QuoteRequested(Box::new(QuoteRequestedData {
+            input_token: Address::repeat_byte(4).to_string(),
+            output_token: Address::repeat_byte(2).to_string(),
+            input_amount: "500".to_string(),
+            chain_id: 1,
+        }))
+    }
+
+    #[test]
+    fn filters_compose() {
+        let filter = FeedFilter::pair(Address::repeat_byte(2), Address::repeat_byte(4))
+            .and(FeedFilter::ChainId(42161))
+            .and(FeedFilter::MinInputAmount(U256::from(1000)));
+
+        assert!(filter.matches(&order_created(1000)));
+        assert!(!filter.matches(&order_created(999)));
+        assert!(!filter.matches(&quote_requested()));
+        assert!(FeedFilter::Offerer(Address::repeat_byte(1)).matches(&order_created(1)));
+        assert!(!FeedFilter::Maker(Address::repeat_byte(1)).matches(&order_created(1)));
+        assert!(FeedFilter::Event(SubscriptionEvents::OrderCreated)
+            .negate()
+            .matches(&quote_requested()));
+    }
+
+    #[test]
+    fn filters_serialize() {
+        let filter = FeedFilter::events([SubscriptionEvents::OrderCreated])
+            .and(FeedFilter::MinOutputAmount(U256::from(1)));
+        let json = serde_json::to_string(&filter).unwrap();
+        assert_eq!(serde_json::from_str::<FeedFilter>(&json).unwrap(), filter);
+    }
+
+    #[tokio::test]
+    async fn router_dispatches_per_variant() {
+        let created = Arc::new(AtomicUsize::new(0));
+        let quotes = Arc::new(AtomicUsize::new(0));
+
+        let (c, q) = (created.clone(), quotes.clone());
+        let router = FeedRouter::new()
+            .with_filter(FeedFilter::ChainId(10).negate())
+            .on_filtered(
+                SubscriptionEvents::OrderCreated,
+                FeedFilter::MinInputAmount(U256::from(1000)),
+                move |_| {
+                    let c = c.clone();
+                    async move {
+                        c.fetch_add(1, Ordering::SeqCst);
+                    }
+                },
+            )
+            .on(SubscriptionEvents::QuoteRequested, move |_| {
+                let q = q.clone();
+                async move {
+                    q.fetch_add(1, Ordering::SeqCst);
+                }
+            });
+
+        router
+            .run(futures::stream::iter(vec![
+                order_created(1),
+                order_created(1000),
+                quote_requested(),
+            ]))
+            .await;
+
+        assert_eq!(created.load(Ordering::SeqCst), 1);
+        assert_eq!(quotes.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,424 @@
+// http client stack
+// builds backend and provider clients on a tower stack, outermost first:
+//
+//     user layers -> tracing -> rate limiting -> timeout -> transport
+//
+// The transport is jsonrpsee's hyper client, or reqwest when going through a proxy
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use hyper::{
+    header::{HeaderName, HeaderValue},
+    Body, HeaderMap,
+};
+use jsonrpsee::http_client::{
+    transport::{Error as TransportError, HttpBackend},
+    HttpClient, HttpClientBuilder,
+};
+use serde_json::Value;
+use tower::{util::BoxCloneService, BoxError, Layer, Service, ServiceBuilder};
+use tracing::{debug, field::Empty, info_span, warn, Instrument, Span};
+
+use super::{
+    error::{sdk_error, AoriError, AoriResult},
+    rate_limit::{RateLimitLayer, RateLimiter},
+};
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+/// jsonrpsee's own limit on a whole call, including time queued by the rate limiter
+const CALL_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub type HttpRequest = hyper::Request<Body>;
+pub type HttpResponse = hyper::Response<Body>;
+pub type AoriHttpClient = HttpClient<HttpService>;
+
+type HttpFuture = Pin<Box<dyn Future<Output = Result<HttpResponse, TransportError>> + Send>>;
+type UserLayer = Arc<dyn Fn(HttpService) -> HttpService + Send + Sync>;
+
+/// A type erased layer of the stack. jsonrpsee needs its service to be `Sync`, which
+/// `BoxCloneService` isn't, so it sits behind a mutex that's only locked to clone it
+pub struct HttpService(Mutex<BoxCloneService<HttpRequest, HttpResponse, TransportError>>);
+
+impl HttpService {
+    pub fn new<S>(service: S) -> Self
+    where
+        S: Service<HttpRequest, Response = HttpResponse, Error = TransportError>
+            + Clone
+            + Send
+            + 'static,
+        S::Future: Send + 'static,
+    {
+        Self(Mutex::new(BoxCloneService::new(service)))
+    }
+}
+
+impl Clone for HttpService {
+    fn clone(&self) -> Self {
+        Self(Mutex::new(self.0.lock().unwrap().clone()))
+    }
+}
+
+impl Service<HttpRequest> for HttpService {
+    type Response = HttpResponse;
+    type Error = TransportError;
+    type Future = HttpFuture;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), TransportError>> {
+        self.0.get_mut().unwrap().poll_ready(cx)
+    }
+
+    fn call(&mut self, request: HttpRequest) -> Self::Future {
+        self.0.get_mut().unwrap().call(request)
+    }
+}
+
+/// How backend and provider clients are built, cheap to clone
+#[derive(Clone)]
+pub struct HttpClientConfig {
+    /// For each HTTP request, not counting time spent waiting on the rate limiter
+    pub timeout: Option<Duration>,
+    /// Sent with every request
+    pub headers: HeaderMap,
+    /// `http://`, `https://` or `socks5://` proxy url
+    pub proxy: Option<String>,
+    pub rate_limiter: Option<RateLimiter>,
+    layers: Vec<UserLayer>,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Some(DEFAULT_TIMEOUT),
+            headers: HeaderMap::new(),
+            proxy: None,
+            rate_limiter: None,
+            layers: vec![],
+        }
+    }
+}
+
+impl HttpClientConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> AoriResult<Self> {
+        let name: HeaderName =
+            name.parse().map_err(|_| AoriError::Config(format!("invalid header {}", name)))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(|_| AoriError::Config(format!("invalid value for header {}", name)))?;
+        self.headers.insert(name, value);
+        Ok(self)
+    }
+
+    pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.proxy = Some(proxy.into());
+        self
+    }
+
+    pub fn rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Adds `layer` outside the built in ones, so it sees each request first. Layers added
+    /// later wrap earlier ones
+    pub fn layer<L>(mut self, layer: L) -> Self
+    where
+        L: Layer<HttpService> + Send + Sync + 'static,
+        L::Service: Service<HttpRequest, Response = HttpResponse, Error = TransportError>
+            + Clone
+            + Send
+            + 'static,
+        <L::Service as Service<HttpRequest>>::Future: Send + 'static,
+    {
+        self.layers.push(Arc::new(move |service| HttpService::new(layer.layer(service))));
+        self
+    }
+
+    pub fn build(&self, url: &str) -> AoriResult<AoriHttpClient> {
+        let proxy = self.proxy.as_deref().map(ProxyTransport::new).transpose()?;
+        let config = self.clone();
+        let stack = move |backend: HttpBackend| {
+            let transport = match &proxy {
+                Some(proxy) => HttpService::new(proxy.clone()),
+                None => HttpService::new(backend),
+            };
+            config.stack(transport)
+        };
+
+        HttpClientBuilder::default()
+            .set_headers(self.headers.clone())
+            .request_timeout(self.timeout.map_or(CALL_TIMEOUT, |timeout| timeout.max(CALL_TIMEOUT)))
+            .set_http_middleware(ServiceBuilder::new().layer_fn(stack))
+            .build(url)
+            .map_err(|e| AoriError::Transport(e.to_string()))
+    }
+
+    fn stack(&self, transport: HttpService) -> HttpService {
+        let mut service = transport;
+        if let Some(timeout) = self.timeout {
+            service = HttpService::new(
+                ServiceBuilder::new()
+                    .map_err(move |e| timeout_error(e, timeout))
+                    .timeout(timeout)
+                    .service(service),
+            );
+        }
+        service = HttpService::new(RateLimitLayer::new(self.rate_limiter.clone()).layer(service));
+        service = HttpService::new(TraceService { inner: service });
+        for layer in &self.layers {
+            service = layer(service);
+        }
+        service
+    }
+}
+
+fn timeout_error(e: BoxError, timeout: Duration) -> TransportError {
+    match e.downcast::<TransportError>() {
+        Ok(e) => *e,
+        Err(e) if e.is::<tower::timeout::error::Elapsed>() => TransportError::Http(Box::new(
+            AoriError::Timeout(format!("no response after {:?}", timeout)),
+        )),
+        Err(e) => TransportError::Http(e),
+    }
+}
+
+/// Runs each request in an `aori_http` span recording its methods, latency and how it failed
+#[derive(Clone)]
+struct TraceService {
+    inner: HttpService,
+}
+
+impl Service<HttpRequest> for TraceService {
+    type Response = HttpResponse;
+    type Error = TransportError;
+    type Future = HttpFuture;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), TransportError>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: HttpRequest) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            let (parts, body) = request.into_parts();
+            let body = read_body(body).await?;
+            let span = info_span!(
+                "aori_http",
+                method = %request_methods(&body).join(","),
+                endpoint = %parts.uri.authority().map(|a| a.as_str()).unwrap_or_default(),
+                latency_ms = Empty,
+                error = Empty,
+            );
+
+            let started = Instant::now();
+            let request = HttpRequest::from_parts(parts, Body::from(body));
+            let result = inner.call(request).instrument(span.clone()).await;
+            span.record("latency_ms", started.elapsed().as_millis() as u64);
+
+            // errors in the JSON-RPC response are left to the client, which maps them to
+            // `AoriError::Backend` or `AoriError::Provider`
+            match result {
+                Ok(response) if !response.status().is_success() => {
+                    failed(&span, "Transport", &response.status().to_string());
+                    Ok(response)
+                }
+                Ok(response) => {
+                    debug!(parent: &span, "request sent");
+                    Ok(response)
+                }
+                Err(e) => {
+                    let variant = match sdk_error(&e) {
+                        Some(AoriError::RateLimited(_)) => "RateLimited",
+                        Some(AoriError::Timeout(_)) => "Timeout",
+                        _ => "Transport",
+                    };
+                    failed(&span, variant, &e.to_string());
+                    Err(e)
+                }
+            }
+        })
+    }
+}
+
+fn failed(span: &Span, variant: &str, message: &str) {
+    span.record("error", variant);
+    warn!(parent: span, "request failed: {}", message);
+}
+
+/// Sends requests with reqwest, which unlike jsonrpsee's client can go through a proxy
+#[derive(Clone)]
+struct ProxyTransport {
+    client: reqwest::Client,
+}
+
+impl ProxyTransport {
+    fn new(proxy: &str) -> AoriResult<Self> {
+        let proxy = reqwest::Proxy::all(proxy)
+            .map_err(|e| AoriError::Config(format!("invalid proxy {}: {}", proxy, e)))?;
+        let client = reqwest::Client::builder()
+            .proxy(proxy)
+            .build()
+            .map_err(|e| AoriError::Transport(e.to_string()))?;
+        Ok(Self { client })
+    }
+}
+
+impl Service<HttpRequest> for ProxyTransport {
+    type Response = HttpResponse;
+    type Error = TransportError;
+    type Future = HttpFuture;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), TransportError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: HttpRequest) -> Self::Future {
+        let client = self.client.clone();
+        Box::pin(async move {
+            let (parts, body) = request.into_parts();
+            let request = hyper::Request::from_parts(parts, read_body(body).await?);
+            let request = reqwest::Request::try_from(request).map_err(http_error)?;
+
+            let response = client.execute(request).await.map_err(http_error)?;
+            let (status, headers) = (response.status(), response.headers().clone());
+            let mut response =
+                HttpResponse::new(Body::from(response.bytes().await.map_err(http_error)?));
+            *response.status_mut() = status;
+            *response.headers_mut() = headers;
+            Ok(response)
+        })
+    }
+}
+
+fn http_error(e: impl std::error::Error + Send + Sync + 'static) -> TransportError {
+    TransportError::Http(Box::new(e))
+}
+
+async fn read_body(body: Body) -> Result<hyper::body::Bytes, TransportError> {
+    hyper::body::to_bytes(body).await.map_err(http_error)
+}
+
+/// Methods called by a JSON-RPC request or batch
+pub(crate) fn request_methods(body: &[u8]) -> Vec<String> {
+    let method = |call: &Value| call.get("method").and_then(Value::as_str).map(String::from);
+    match serde_json::from_slice(body) {
+        Ok(Value::Array(calls)) => calls.iter().filter_map(method).collect(),
+        Ok(call) => method(&call).into_iter().collect(),
+        Err(_) => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AoriPingParams, ProviderClient};
+    use jsonrpsee::{
+        core::client::ClientT,
+        rpc_params,
+        server::{RpcModule, ServerHandle},
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Answers pings, and `aori_slow` after half a second
+    async fn mock_server() -> (String, ServerHandle) {
+        let mut module = RpcModule::new(());
+        module.register_method("aori_ping", |_, _| "aori_pong").unwrap();
+        module
+            .register_async_method("aori_slow", |_, _| async {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                "done"
+            })
+            .unwrap();
+
+        crate::test_utils::mock_server(module).await
+    }
+
+    /// Counts requests and checks they carry the configured header
+    #[derive(Clone)]
+    struct Counter {
+        inner: HttpService,
+        count: Arc<AtomicUsize>,
+    }
+
+    impl Service<HttpRequest> for Counter {
+        type Response = HttpResponse;
+        type Error = TransportError;
+        type Future = HttpFuture;
+
+        fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), TransportError>> {
+            self.inner.poll_ready(cx)
+        }
+
+        fn call(&mut self, request: HttpRequest) -> Self::Future {
+            assert_eq!(request.headers()["x-api-key"], "key");
+            self.count.fetch_add(1, Ordering::SeqCst);
+            self.inner.call(request)
+        }
+    }
+
+    #[tokio::test]
+    async fn builds_clients_on_the_stack() {
+        let (url, handle) = mock_server().await;
+        let (count, built) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let (counter, builds) = (count.clone(), built.clone());
+        let config = HttpClientConfig::new()
+            .timeout(Some(Duration::from_millis(100)))
+            .header("x-api-key", "key")
+            .unwrap()
+            .layer(tower::layer::layer_fn(move |inner| {
+                builds.fetch_add(1, Ordering::SeqCst);
+                Counter { inner, count: counter.clone() }
+            }));
+
+        // the provider and its clones build the client once
+        let provider = ProviderClient::new(vec![url.clone()]).with_http_config(config.clone());
+        assert_eq!(provider.ping().await.unwrap(), "aori_pong");
+        assert_eq!(provider.clone().ping().await.unwrap(), "aori_pong");
+        assert_eq!(built.load(Ordering::SeqCst), 1);
+
+        let backend = config.build(&url).unwrap();
+        let slow = backend.request::<String, _>("aori_slow", rpc_params![]).await;
+        assert!(matches!(AoriError::from_backend(slow.unwrap_err()), AoriError::Timeout(_)));
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+        assert_eq!(built.load(Ordering::SeqCst), 2);
+
+        assert!(HttpClientConfig::new().header("bad header", "key").is_err());
+        assert!(HttpClientConfig::new().proxy("not a url").build(&url).is_err());
+
+        handle.stop().unwrap();
+    }
+
+    #[tokio::test]
+    async fn sends_through_proxy_transport() {
+        let (url, handle) = mock_server().await;
+        // the server takes proxied requests too, so it stands in for the proxy of a host that
+        // doesn't exist
+        let client = HttpClientConfig::new().proxy(url).build("http://aori.invalid").unwrap();
+        let pong: String = client.request("aori_ping", [AoriPingParams::default()]).await.unwrap();
+        assert_eq!(pong, "aori_pong");
+
+        handle.stop().unwrap();
+    }
+
+    #[test]
+    fn reads_request_methods() {
+        assert_eq!(request_methods(br#"[{"method":"a"},{"method":"b"}]"#), ["a", "b"]);
+        assert_eq!(request_methods(br#"{"method":"aori_ping","params":[]}"#), ["aori_ping"]);
+        assert!(request_methods(b"not json").is_empty());
+    }
+}
@@ -0,0 +1,205 @@
+// json-rpc envelopes
+// wire encoding for `AoriRequest` and the matching responses, for raw HTTP and WebSocket use
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{
+    error::{AoriBackendErrors, AoriError, AoriResult},
+    request::{aori_methods, AORI_REQUEST_METHODS},
+    AoriAccountBalanceResponse, AoriAccountOrdersResponse, AoriCancelAllOrdersResponse,
+    AoriCancelOrderResponse, AoriErrorData, AoriErrorResponse, AoriMakeOrderResponse,
+    AoriPingResponse, AoriRequest, AoriRequestQuoteResponse, AoriRequestin, AoriTakeOrderResponse,
+    AoriViewOrderbookResponse,
+};
+
+pub const JSONRPC_VERSION: &str = "2.0";
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+/// Used by the backend for every application error, told apart by message
+pub const SERVER_ERROR: i64 = -32000;
+
+macro_rules! response_enum {
+    ($($variant:ident => $method:literal, $request:ident, $response:ty;)*) => {
+        /// A successful response, typed by the method of the request it answers. The JSON alone
+        /// doesn't say which variant it is, so parse it with `decode_response`
+        #[derive(Serialize, Debug)]
+        #[serde(untagged)]
+        pub enum AoriResponse {
+            $($variant($response),)*
+        }
+
+        impl AoriResponse {
+            pub fn id(&self) -> i64 {
+                match self {
+                    $(AoriResponse::$variant(response) => response.id,)*
+                }
+            }
+        }
+    };
+}
+aori_methods!(response_enum);
+
+impl AoriErrorResponse {
+    pub fn new(id: i64, code: i64, message: impl Into<String>) -> Self {
+        Self { id, error: AoriErrorData { code, message: message.into() } }
+    }
+
+    /// Maps an SDK error onto a JSON-RPC error. Backend errors keep the backend's message so
+    /// `AoriBackendErrors::from_error_message` recovers them on the other side
+    pub fn from_error(id: i64, error: &AoriError) -> Self {
+        match error {
+            AoriError::Backend(e) => Self::new(id, SERVER_ERROR, e.to_string()),
+            AoriError::Validation(e) => Self::new(id, INVALID_PARAMS, e.to_string()),
+            AoriError::Encoding(e) => Self::new(id, INVALID_PARAMS, e.clone()),
+            e => Self::new(id, INTERNAL_ERROR, e.to_string()),
+        }
+    }
+
+    pub fn into_error(self) -> AoriError {
+        AoriError::Backend(AoriBackendErrors::from_error_message(&self.error.message))
+    }
+}
+
+pub fn encode_request(request: &AoriRequest) -> AoriResult<String> {
+    Ok(serde_json::to_string(request)?)
+}
+
+/// Parses an incoming request, answering with the error response to send back when it can't
+/// be handled
+pub fn decode_request(json: &str) -> Result<AoriRequest, AoriErrorResponse> {
+    let value: Value = serde_json::from_str(json)
+        .map_err(|e| AoriErrorResponse::new(0, PARSE_ERROR, e.to_string()))?;
+    let id = value.get("id").and_then(Value::as_i64).unwrap_or_default();
+
+    let request: AoriRequestin = serde_json::from_value(value)
+        .map_err(|e| AoriErrorResponse::new(id, INVALID_REQUEST, e.to_string()))?;
+    if request.jsonrpc != JSONRPC_VERSION {
+        return Err(AoriErrorResponse::new(id, INVALID_REQUEST, "jsonrpc must be \"2.0\""));
+    }
+    if !AORI_REQUEST_METHODS.contains(&request.method.as_str()) {
+        let message = format!("Method {} not found", request.method);
+        return Err(AoriErrorResponse::new(id, METHOD_NOT_FOUND, message));
+    }
+
+    AoriRequest::try_from(request).map_err(|e| AoriErrorResponse::from_error(id, &e))
+}
+
+/// Adds the `jsonrpc` member the response structs leave out
+pub fn encode_response(response: &AoriResponse) -> AoriResult<String> {
+    with_version(serde_json::to_value(response)?)
+}
+
+pub fn encode_error_response(response: &AoriErrorResponse) -> AoriResult<String> {
+    with_version(serde_json::to_value(response)?)
+}
+
+/// Parses the response to a `method` call. Error responses become `AoriError::Backend`
+pub fn decode_response(method: &str, json: &str) -> AoriResult<AoriResponse> {
+    let value: Value = serde_json::from_str(json)?;
+    if value.get("error").is_some_and(|error| !error.is_null()) {
+        let error: AoriErrorResponse = serde_json::from_value(value)?;
+        return Err(error.into_error());
+    }
+
+    macro_rules! dispatch {
+        ($($variant:ident => $method:literal, $request:ident, $response:ty;)*) => {
+            match method {
+                $($method => AoriResponse::$variant(serde_json::from_value(value)?),)*
+                _ => return Err(AoriError::Encoding(format!("Unknown method {}", method))),
+            }
+        };
+    }
+
+    Ok(aori_methods!(dispatch))
+}
+
+fn with_version(mut value: Value) -> AoriResult<String> {
+    if let Value::Object(object) = &mut value {
+        object.insert("jsonrpc".to_string(), Value::String(JSONRPC_VERSION.to_string()));
+    }
+    Ok(serde_json::to_string(&value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AoriCancelAllOrdersParams, AoriCancelAllOrdersRequest};
+    use serde_json::json;
+
+    #[test]
+    fn requests_round_trip_as_wire_json_rpc() {
+        let params = AoriCancelAllOrdersParams { api_key: "key".into(), tag: None };
+        let envelope = AoriRequestin::new(3, "aori_cancelAllOrders", params).unwrap();
+        let request = AoriRequest::try_from(envelope).unwrap();
+
+        let wire: Value = serde_json::from_str(&encode_request(&request).unwrap()).unwrap();
+        assert_eq!(
+            wire,
+            json!({
+                "id": 3,
+                "jsonrpc": "2.0",
+                "method": "aori_cancelAllOrders",
+                "params": [{ "apiKey": "key" }],
+            })
+        );
+
+        let decoded = decode_request(&wire.to_string()).unwrap();
+        assert!(matches!(
+            decoded,
+            AoriRequest::CancelAllOrders(AoriCancelAllOrdersRequest { id: 3, .. })
+        ));
+        let deserialized: AoriRequest = serde_json::from_value(wire).unwrap();
+        assert_eq!(deserialized.method(), "aori_cancelAllOrders");
+    }
+
+    #[test]
+    fn rejects_bad_requests_with_json_rpc_codes() {
+        let code = |json: &str| decode_request(json).unwrap_err().error.code;
+        assert_eq!(code("{"), PARSE_ERROR);
+        assert_eq!(code(r#"{"id":1,"method":"aori_ping"}"#), INVALID_REQUEST);
+        assert_eq!(
+            code(r#"{"id":1,"jsonrpc":"1.0","method":"aori_ping","params":[]}"#),
+            INVALID_REQUEST
+        );
+        assert_eq!(
+            code(r#"{"id":1,"jsonrpc":"2.0","method":"aori_nope","params":[]}"#),
+            METHOD_NOT_FOUND
+        );
+        assert_eq!(
+            code(r#"{"id":1,"jsonrpc":"2.0","method":"aori_cancelOrder","params":[{}]}"#),
+            INVALID_PARAMS
+        );
+        assert_eq!(decode_request(r#"{"id":9,"method":"aori_ping"}"#).unwrap_err().id, 9);
+    }
+
+    #[test]
+    fn decodes_responses_and_errors() {
+        let response =
+            decode_response("aori_ping", r#"{"id":1,"jsonrpc":"2.0","result":"aori_pong"}"#)
+                .unwrap();
+        assert!(matches!(&response, AoriResponse::Ping(ping) if ping.result == "aori_pong"));
+        let with_null_error = r#"{"id":1,"jsonrpc":"2.0","result":"aori_pong","error":null}"#;
+        assert!(matches!(decode_response("aori_ping", with_null_error), Ok(AoriResponse::Ping(_))));
+        let encoded: Value = serde_json::from_str(&encode_response(&response).unwrap()).unwrap();
+        assert_eq!(encoded, json!({ "id": 1, "jsonrpc": "2.0", "result": "aori_pong" }));
+
+        let error = AoriErrorResponse::from_error(2, &AoriBackendErrors::InvalidAPIKey().into());
+        let wire = encode_error_response(&error).unwrap();
+        assert!(matches!(
+            decode_response("aori_cancelOrder", &wire),
+            Err(AoriError::Backend(AoriBackendErrors::InvalidAPIKey()))
+        ));
+        assert!(decode_response("aori_nope", r#"{"id":1,"result":""}"#).is_err());
+
+        // every method in the table decodes to its own variant
+        for method in AORI_REQUEST_METHODS {
+            let error = decode_response(method, r#"{"id":1}"#).unwrap_err();
+            assert!(!error.to_string().contains("Unknown method"), "{}: {}", method, error);
+        }
+    }
+}
@@ -0,0 +1,287 @@
+// cancel-all-by-counter
+// invalidates every order signed for a zone on-chain before asking the backend to drop them
+
+use std::time::Duration;
+
+use alloy_primitives::{Address, B256, U256};
+use ethers::signers::{LocalWallet, Signer};
+use tokio::time::{sleep, Instant};
+
+use super::{
+    chains::ChainId,
+    client::AoriBackendRpcClient,
+    contract::{parse_quantity, AoriV2Contract},
+    error::{AoriError, AoriResult},
+    settlement::ChainNode,
+    zones::ZoneRegistry,
+    AoriCancelAllOrdersParams,
+};
+
+#[derive(Clone, Debug)]
+pub struct KillSwitchOptions {
+    pub poll_interval: Duration,
+    /// How long to wait for the counter to advance and reach `confirmations`
+    pub timeout: Duration,
+    pub confirmations: u64,
+    pub tag: Option<String>,
+}
+
+impl KillSwitchOptions {
+    /// Polls once per block and waits for the chain's usual confirmation depth
+    pub fn for_chain(chain: ChainId) -> Self {
+        let info = chain.info();
+        Self {
+            poll_interval: info.block_time,
+            timeout: Duration::from_secs(120),
+            confirmations: info.confirmations,
+            tag: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct KillSwitchReport {
+    pub transaction_hash: B256,
+    /// Orders signed for this zone with a counter up to this value are now unfillable. The
+    /// counter is kept per zone, so orders on other zones are untouched
+    pub previous_counter: U256,
+    pub counter: U256,
+    pub confirmed_at_block: u64,
+    /// Set if `aori_cancelAllOrders` failed. The orders are invalid on-chain regardless,
+    /// but may linger in the orderbook until the backend notices
+    pub backend_error: Option<AoriError>,
+}
+
+/// Increments the signer's counter on `contract`'s zone, waits for it to be confirmed, then
+/// cancels the signer's orders on the backend and checks the counter really moved. The zone's
+/// counter is read with `eth_call`s through `node`, a node of the contract's chain
+pub async fn cancel_all_by_counter<C>(
+    contract: &AoriV2Contract,
+    node: &ChainNode,
+    backend: &C,
+    wallet: &LocalWallet,
+    api_key: String,
+    options: KillSwitchOptions,
+) -> AoriResult<KillSwitchReport>
+where
+    C: AoriBackendRpcClient + Sync,
+{
+    let provider = contract.provider();
+    let chain_id = contract.chain_id;
+    let from = Address::from(wallet.address().0);
+    let address = format!("{:?}", from);
+    let counter = || async {
+        let data = node.call(&contract.get_counter(), from).await?;
+        AoriV2Contract::decode_get_counter(&data)
+    };
+    let block_number = || async {
+        let response = provider.get_block_number(chain_id).await?;
+        parse_quantity(&response.result).map(|n| n.saturating_to::<u64>())
+    };
+
+    let previous_counter = counter().await?;
+    let transaction_hash = contract.send(&contract.increment_counter(), wallet).await?;
+
+    let deadline = Instant::now() + options.timeout;
+    let included_at = loop {
+        if counter().await? > previous_counter {
+            break block_number().await?;
+        }
+        wait_until_next_poll(deadline, options.poll_interval, transaction_hash).await?;
+    };
+
+    let target = included_at + options.confirmations.saturating_sub(1);
+    let confirmed_at_block = loop {
+        let current = block_number().await?;
+        if current >= target {
+            break current;
+        }
+        wait_until_next_poll(deadline, options.poll_interval, transaction_hash).await?;
+    };
+
+    let backend_error = backend
+        .cancel_all_orders(AoriCancelAllOrdersParams { api_key, tag: options.tag })
+        .await
+        .err()
+        .map(AoriError::from_backend);
+
+    let counter = counter().await?;
+    if counter <= previous_counter {
+        return Err(AoriError::Provider(format!(
+            "Counter for {} is still {} after {}",
+            address, counter, transaction_hash
+        )));
+    }
+
+    // the data provider only tracks the default zone's counter
+    let default_zone = ChainId::try_from(chain_id)
+        .ok()
+        .and_then(|chain| ZoneRegistry::builtin().default_zone(chain));
+    if default_zone == Some(contract.address) {
+        let response = provider.get_aori_counter(chain_id, address.clone()).await?;
+        let provider_counter = parse_quantity(&response.result)?;
+        if provider_counter < counter {
+            return Err(AoriError::Provider(format!(
+                "Data provider still reports counter {} for {} but the zone has {}",
+                provider_counter, address, counter
+            )));
+        }
+    }
+
+    Ok(KillSwitchReport {
+        transaction_hash,
+        previous_counter,
+        counter,
+        confirmed_at_block,
+        backend_error,
+    })
+}
+
+async fn wait_until_next_poll(
+    deadline: Instant,
+    poll_interval: Duration,
+    transaction_hash: B256,
+) -> AoriResult<()> {
+    if Instant::now() + poll_interval > deadline {
+        return Err(AoriError::Timeout(format!(
+            "incrementCounter transaction {} was not confirmed in time",
+            transaction_hash
+        )));
+    }
+    sleep(poll_interval).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{provider::ProviderClient, test_utils::mock_server};
+    use alloy_primitives::Bytes;
+    use ethers::{types::transaction::eip2718::TypedTransaction, utils::rlp::Rlp};
+    use jsonrpsee::{http_client::HttpClientBuilder, server::RpcModule};
+    use serde_json::{json, Value};
+    use std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Mutex,
+        },
+    };
+
+    /// Keeps one counter per zone; the data provider only reports the default zone's
+    struct MockChain {
+        counters: Mutex<HashMap<Address, u64>>,
+        default_zone: Address,
+        block: AtomicU64,
+        cancelled: AtomicU64,
+    }
+
+    impl MockChain {
+        fn new() -> Self {
+            Self {
+                counters: Mutex::default(),
+                default_zone: ZoneRegistry::builtin().default_zone(ChainId::Arbitrum).unwrap(),
+                block: AtomicU64::new(0),
+                cancelled: AtomicU64::new(0),
+            }
+        }
+
+        fn counter(&self, zone: Address) -> u64 {
+            self.counters.lock().unwrap().get(&zone).copied().unwrap_or_default()
+        }
+    }
+
+    fn mock_module(chain: Arc<MockChain>) -> RpcModule<Arc<MockChain>> {
+        let mut module = RpcModule::new(chain);
+        let quantity = |result: String| json!({ "id": 1, "result": result });
+
+        module
+            .register_method("eth_call", |params, chain| {
+                let (request, _): (Value, String) = params.parse().unwrap();
+                let zone: Address = request["to"].as_str().unwrap().parse().unwrap();
+                json!(Bytes::from(U256::from(chain.counter(zone)).to_be_bytes::<32>()))
+            })
+            .unwrap();
+        module
+            .register_method("aori_getAoriCounter", move |_, chain| {
+                quantity(chain.counter(chain.default_zone).to_string())
+            })
+            .unwrap();
+        module
+            .register_method("aori_getBlockNumber", move |_, chain| {
+                quantity(chain.block.fetch_add(1, Ordering::SeqCst).to_string())
+            })
+            .unwrap();
+        module.register_method("aori_getNonce", move |_, _| quantity("0".to_string())).unwrap();
+        module
+            .register_method("aori_estimateGas", move |_, _| quantity("50000".to_string()))
+            .unwrap();
+        module
+            .register_method("aori_getFeeData", move |_, _| quantity("0x3b9aca00".to_string()))
+            .unwrap();
+        module
+            .register_method("aori_sendTransaction", move |params, chain| {
+                let params: Value = params.sequence().next().unwrap();
+                let raw: Bytes = params["signedTx"].as_str().unwrap().parse().unwrap();
+                let (tx, _) = TypedTransaction::decode_signed(&Rlp::new(&raw)).unwrap();
+                let zone = Address::from(tx.to_addr().unwrap().0);
+                *chain.counters.lock().unwrap().entry(zone).or_default() += 1;
+                quantity(B256::repeat_byte(0xab).to_string())
+            })
+            .unwrap();
+        module
+            .register_method("aori_cancelAllOrders", move |_, chain| {
+                chain.cancelled.fetch_add(1, Ordering::SeqCst);
+            })
+            .unwrap();
+        module
+    }
+
+    async fn kill(zone: Address, chain: Arc<MockChain>) -> AoriResult<KillSwitchReport> {
+        let (url, handle) = mock_server(mock_module(chain)).await;
+
+        let provider = ProviderClient::new(vec![url.clone()]);
+        let contract = AoriV2Contract::new(zone, 42161, provider);
+        let node = ChainNode::new(&url).unwrap();
+        let backend = HttpClientBuilder::default().build(&url).unwrap();
+        let wallet: LocalWallet =
+            "0000000000000000000000000000000000000000000000000000000000000001".parse().unwrap();
+
+        let options = KillSwitchOptions {
+            poll_interval: Duration::from_millis(10),
+            confirmations: 3,
+            ..KillSwitchOptions::for_chain(ChainId::Arbitrum)
+        };
+        let report =
+            cancel_all_by_counter(&contract, &node, &backend, &wallet, "key".into(), options).await;
+        handle.stop().unwrap();
+        report
+    }
+
+    #[tokio::test]
+    async fn increments_counter_then_cancels_on_backend() {
+        let chain = Arc::new(MockChain::new());
+        let report = kill(chain.default_zone, chain.clone()).await.unwrap();
+
+        assert_eq!(report.transaction_hash, B256::repeat_byte(0xab));
+        assert_eq!(report.previous_counter, U256::ZERO);
+        assert_eq!(report.counter, U256::from(1));
+        assert!(report.confirmed_at_block >= 2);
+        assert!(report.backend_error.is_none());
+        assert_eq!(chain.cancelled.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn watches_the_counter_of_its_own_zone() {
+        let chain = Arc::new(MockChain::new());
+        let zone = Address::repeat_byte(3);
+        chain.counters.lock().unwrap().insert(zone, 4);
+
+        let report = kill(zone, chain.clone()).await.unwrap();
+
+        assert_eq!(report.previous_counter, U256::from(4));
+        assert_eq!(report.counter, U256::from(5));
+        assert_eq!(chain.counter(chain.default_zone), 0);
+        assert_eq!(chain.cancelled.load(Ordering::SeqCst), 1);
+    }
+}
@@ -1,18 +1,38 @@
+pub mod batch;
+pub mod bson_codec;
 pub mod builder;
+pub mod chains;
 pub mod client;
+pub mod config;
 pub mod constants;
+pub mod contract;
 pub mod error;
+pub mod filter;
+pub mod http_client;
+pub mod jsonrpc;
+pub mod kill_switch;
 
 pub mod provider;
+pub mod rate;
+pub mod rate_limit;
+pub mod relay;
 pub mod request;
 pub mod response;
+pub mod seats;
+pub mod settlement;
 pub mod shared_types;
 pub mod signature;
+pub mod store;
 pub mod subscription;
+#[cfg(test)]
+pub(crate) mod test_utils;
+pub mod zones;
 
+pub use batch::AoriBackendBatchClient;
 pub use client::AoriBackendRpcClient;
-pub use error::AoriBackendErrors;
+pub use error::{AoriBackendErrors, AoriError, AoriResult, ContractError, ValidationError};
 pub use provider::*;
+pub use rate::Rate;
 pub use request::*;
 pub use response::*;
 pub use shared_types::*;
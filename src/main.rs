@@ -1,4 +0,0 @@
-#[tokio::main]
-async fn main() {
-    println!("hi");
-}
@@ -1,15 +1,28 @@
-use jsonrpsee::{
-    http_client::{HttpClient, HttpClientBuilder},
-    proc_macros::rpc,
-};
+use std::sync::{Arc, OnceLock};
+
+#[cfg(feature = "server")]
+use jsonrpsee::core::RpcResult;
+use jsonrpsee::proc_macros::rpc;
 use serde::{Deserialize, Serialize};
-use tokio_tungstenite::tungstenite::http::method;
 
-use super::{AoriPingParams, AoriPingResponse};
+use super::{
+    batch::batch_request,
+    config::DeploymentConfig,
+    constants::AORI_PROVIDER_URL,
+    error::{AoriError, AoriResult},
+    http_client::{AoriHttpClient, HttpClientConfig},
+    rate_limit::RateLimiter,
+    seats::SeatDetails,
+    AoriPingParams,
+};
 
+/// Clones share one HTTP client, built on first use. Set `urls` and `http` before that, or
+/// through `with_http_config` and `with_rate_limiter`, which start a new one
 #[derive(Clone)]
 pub struct ProviderClient {
     pub urls: Vec<String>,
+    pub http: HttpClientConfig,
+    client: Arc<OnceLock<AoriHttpClient>>,
 }
 
 ////////////////////////////////////////////////////////////////
@@ -190,7 +203,7 @@ pub struct AoriGetAoriCounterResponse {
 #[serde(rename_all = "camelCase")]
 pub struct AoriGetSeatDetailsResponse {
     pub id: u64,
-    pub result: String,
+    pub result: SeatDetails,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -214,7 +227,8 @@ pub struct AoriSendTransactionResponse {
     pub result: String,
 }
 
-#[rpc(client)]
+#[cfg_attr(not(feature = "server"), rpc(client))]
+#[cfg_attr(feature = "server", rpc(server, client))]
 pub trait AoriDataProviderRpc {
     #[method(name = "aori_ping")]
     async fn ping_provider(&self, parameters: AoriPingParams) -> RpcResult<String>;
@@ -284,43 +298,69 @@ pub trait AoriDataProviderRpc {
 
 impl ProviderClient {
     pub fn new(urls: Vec<String>) -> Self {
-        Self { urls }
+        Self { urls, http: HttpClientConfig::default(), client: Arc::default() }
     }
 
-    pub fn get_client(&self) -> HttpClient {
-        let url = "https://provider.aori.io";
-        HttpClientBuilder::default().build(url).unwrap()
+    pub fn from_config(config: &DeploymentConfig) -> Self {
+        Self::new(vec![config.provider_url.clone()])
+    }
+
+    pub fn with_http_config(mut self, http: HttpClientConfig) -> Self {
+        self.http = http;
+        self.client = Arc::default();
+        self
     }
 
-    pub async fn ping(&self) -> Result<String, anyhow::Error> {
-        let client = self.get_client();
+    /// Holds requests back according to `limiter`, which may be shared with other clients
+    pub fn with_rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.http = self.http.rate_limiter(limiter);
+        self.client = Arc::default();
+        self
+    }
+
+    /// Client for the first configured url, or the production provider if none are set
+    pub fn get_client(&self) -> AoriResult<&AoriHttpClient> {
+        if let Some(client) = self.client.get() {
+            return Ok(client);
+        }
+        let url = self.urls.first().map(String::as_str).unwrap_or(AORI_PROVIDER_URL);
+        let client = self.http.build(url)?;
+        // a concurrent first call may have won, either client is as good
+        Ok(self.client.get_or_init(|| client))
+    }
+
+    pub async fn ping(&self) -> AoriResult<String> {
+        let client = self.get_client()?;
         let request = AoriPingParams::default();
-        Ok(client.ping_provider(request).await?)
+        client.ping_provider(request).await.map_err(AoriError::from_provider)
     }
 
-    pub async fn get_block_number(
-        &self,
-        chain_id: u64,
-    ) -> Result<AoriGetBlockNumberResponse, anyhow::Error> {
-        let client = self.get_client();
-        Ok(client.get_block_number(AoriGetBlockNumberParams { chain_id }).await?)
+    pub async fn get_block_number(&self, chain_id: u64) -> AoriResult<AoriGetBlockNumberResponse> {
+        let client = self.get_client()?;
+        client
+            .get_block_number(AoriGetBlockNumberParams { chain_id })
+            .await
+            .map_err(AoriError::from_provider)
     }
 
     pub async fn get_nonce(
         &self,
         address: String,
         chain_id: u64,
-    ) -> Result<AoriGetNonceResponse, anyhow::Error> {
-        let client = self.get_client();
-        Ok(client.get_nonce(AoriGetNonceParams { address, chain_id }).await?)
+    ) -> AoriResult<AoriGetNonceResponse> {
+        let client = self.get_client()?;
+        client
+            .get_nonce(AoriGetNonceParams { address, chain_id })
+            .await
+            .map_err(AoriError::from_provider)
     }
 
-    pub async fn get_fee_data(
-        &self,
-        chain_id: u64,
-    ) -> Result<AoriGetFeeDataResponse, anyhow::Error> {
-        let client = self.get_client();
-        Ok(client.get_fee_data(AoriGetFeeDataParams { chain_id }).await?)
+    pub async fn get_fee_data(&self, chain_id: u64) -> AoriResult<AoriGetFeeDataResponse> {
+        let client = self.get_client()?;
+        client
+            .get_fee_data(AoriGetFeeDataParams { chain_id })
+            .await
+            .map_err(AoriError::from_provider)
     }
 
     pub async fn estimate_gas(
@@ -330,9 +370,12 @@ impl ProviderClient {
         value: String,
         data: String,
         chain_id: u64,
-    ) -> Result<AoriEstimateGasResponse, anyhow::Error> {
-        let client = self.get_client();
-        Ok(client.estimate_gas(AoriEstimateGasParams { from, to, value, data, chain_id }).await?)
+    ) -> AoriResult<AoriEstimateGasResponse> {
+        let client = self.get_client()?;
+        client
+            .estimate_gas(AoriEstimateGasParams { from, to, value, data, chain_id })
+            .await
+            .map_err(AoriError::from_provider)
     }
 
     pub async fn is_valid_signature() {}
@@ -342,29 +385,36 @@ impl ProviderClient {
         order_hash: String,
         zone: String,
         chain_id: u64,
-    ) -> Result<AoriHasOrderSettledResponse, anyhow::Error> {
-        let client = self.get_client();
-        Ok(client
+    ) -> AoriResult<AoriHasOrderSettledResponse> {
+        let client = self.get_client()?;
+        client
             .has_order_settled(AoriHasOrderSettledParams { order_hash, zone, chain_id })
-            .await?)
+            .await
+            .map_err(AoriError::from_provider)
     }
 
     pub async fn get_aori_counter(
         &self,
         chain_id: u64,
         address: String,
-    ) -> Result<AoriGetAoriCounterResponse, anyhow::Error> {
-        let client = self.get_client();
-        Ok(client.get_aori_counter(AoriGetAoriCounterParams { chain_id, address }).await?)
+    ) -> AoriResult<AoriGetAoriCounterResponse> {
+        let client = self.get_client()?;
+        client
+            .get_aori_counter(AoriGetAoriCounterParams { chain_id, address })
+            .await
+            .map_err(AoriError::from_provider)
     }
 
     pub async fn get_native_balance(
         &self,
         chain_id: u64,
         address: String,
-    ) -> Result<AoriGetNativeBalanceResponse, anyhow::Error> {
-        let client = self.get_client();
-        Ok(client.get_native_balance(AoriGetNativeBalanceParams { chain_id, address }).await?)
+    ) -> AoriResult<AoriGetNativeBalanceResponse> {
+        let client = self.get_client()?;
+        client
+            .get_native_balance(AoriGetNativeBalanceParams { chain_id, address })
+            .await
+            .map_err(AoriError::from_provider)
     }
 
     pub async fn get_token_balance(
@@ -372,9 +422,12 @@ impl ProviderClient {
         chain_id: u64,
         owner: String,
         token: String,
-    ) -> Result<AoriGetTokenBalanceResponse, anyhow::Error> {
-        let client = self.get_client();
-        Ok(client.get_token_balance(AoriGetTokenBalanceParams { owner, token, chain_id }).await?)
+    ) -> AoriResult<AoriGetTokenBalanceResponse> {
+        let client = self.get_client()?;
+        client
+            .get_token_balance(AoriGetTokenBalanceParams { owner, token, chain_id })
+            .await
+            .map_err(AoriError::from_provider)
     }
 
     pub async fn get_token_allowance(
@@ -383,45 +436,98 @@ impl ProviderClient {
         spender: String,
         token: String,
         chain_id: u64,
-    ) -> Result<AoriGetTokenAllowanceResponse, anyhow::Error> {
-        let client = self.get_client();
-        Ok(client
+    ) -> AoriResult<AoriGetTokenAllowanceResponse> {
+        let client = self.get_client()?;
+        client
             .get_token_allowance(AoriGetTokenAllowanceParams { owner, spender, token, chain_id })
-            .await?)
+            .await
+            .map_err(AoriError::from_provider)
+    }
+
+    /// `get_token_balance` for every item, sent as JSON-RPC batches over one client. Results
+    /// are in input order, see `batch::batch_request`
+    pub async fn get_token_balances(
+        &self,
+        balances: Vec<AoriGetTokenBalanceParams>,
+    ) -> AoriResult<Vec<AoriResult<AoriGetTokenBalanceResponse>>> {
+        let client = self.get_client()?;
+        batch_request(client, "aori_getTokenBalance", balances, AoriError::from_provider).await
+    }
+
+    pub async fn get_token_allowances(
+        &self,
+        allowances: Vec<AoriGetTokenAllowanceParams>,
+    ) -> AoriResult<Vec<AoriResult<AoriGetTokenAllowanceResponse>>> {
+        let client = self.get_client()?;
+        batch_request(client, "aori_getTokenAllowance", allowances, AoriError::from_provider).await
+    }
+
+    pub async fn has_orders_settled(
+        &self,
+        orders: Vec<AoriHasOrderSettledParams>,
+    ) -> AoriResult<Vec<AoriResult<AoriHasOrderSettledResponse>>> {
+        let client = self.get_client()?;
+        batch_request(client, "aori_hasOrderSettled", orders, AoriError::from_provider).await
     }
 
     pub async fn get_token_details(
         &self,
         token: String,
         chain_id: u64,
-    ) -> Result<AoriGetTokenDetailsResponse, anyhow::Error> {
-        let client = self.get_client();
-        Ok(client.get_token_details(AoriGetTokenDetailsParams { token, chain_id }).await?)
+    ) -> AoriResult<AoriGetTokenDetailsResponse> {
+        let client = self.get_client()?;
+        client
+            .get_token_details(AoriGetTokenDetailsParams { token, chain_id })
+            .await
+            .map_err(AoriError::from_provider)
     }
 
     pub async fn simulate_transaction(
         &self,
         signed_tx: String,
-    ) -> Result<AoriSimulateTransactionResponse, anyhow::Error> {
-        let client = self.get_client();
-        Ok(client.simulate_transaction(AoriSimulateTransactionParams { signed_tx }).await?)
+    ) -> AoriResult<AoriSimulateTransactionResponse> {
+        let client = self.get_client()?;
+        client
+            .simulate_transaction(AoriSimulateTransactionParams { signed_tx })
+            .await
+            .map_err(AoriError::from_provider)
     }
 
-    // TODO: implement
-    pub async fn get_seat_details(
+    pub async fn get_seat_details(&self, seat_id: u64) -> AoriResult<SeatDetails> {
+        let client = self.get_client()?;
+        client
+            .get_seat_details(AoriGetSeatDetailsParams { seat_id })
+            .await
+            .map(|response| response.result)
+            .map_err(AoriError::from_provider)
+    }
+
+    /// `get_seat_details` for every seat id, sent as JSON-RPC batches over one client
+    pub async fn get_seats_details(
         &self,
-        seat_id: u64,
-    ) -> Result<AoriGetSeatDetailsResponse, anyhow::Error> {
-        let client = self.get_client();
-        Ok(client.get_seat_details(AoriGetSeatDetailsParams { seat_id }).await?)
+        seat_ids: impl IntoIterator<Item = u64>,
+    ) -> AoriResult<Vec<AoriResult<SeatDetails>>> {
+        let client = self.get_client()?;
+        let params = seat_ids.into_iter().map(|seat_id| AoriGetSeatDetailsParams { seat_id });
+        let responses: Vec<AoriResult<AoriGetSeatDetailsResponse>> = batch_request(
+            client,
+            "aori_getSeatDetails",
+            params.collect(),
+            AoriError::from_provider,
+        )
+        .await?;
+        Ok(responses.into_iter().map(|response| response.map(|r| r.result)).collect())
     }
 
     pub async fn send_transaction(
         &self,
         signed_tx: String,
-    ) -> Result<AoriSendTransactionResponse, anyhow::Error> {
-        let client = self.get_client();
-        Ok(client.send_transaction(AoriSendTransactionParams { signed_tx }).await?)
+    ) -> AoriResult<AoriSendTransactionResponse> {
+        let client = self.get_client()?;
+        client
+            .send_transaction(AoriSendTransactionParams { signed_tx })
+            .await
+            .map_err(AoriError::from_provider)
     }
 
     // TODO: implement
@@ -0,0 +1,341 @@
+// exchange rates
+// exact output-per-input prices, scaled by token decimals
+
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use alloy_primitives::{U256, U512};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{
+    error::{AoriError, AoriResult},
+    AoriOrder,
+};
+
+/// Fractional digits shown by `Display`. The rate itself is exact
+pub const RATE_DISPLAY_DECIMALS: usize = 18;
+
+/// Rates needing more fractional digits than this are written as a fraction by `exact`
+const MAX_EXACT_DECIMALS: usize = 64;
+
+/// Units of output token received per unit of input token, kept as an exact fraction.
+/// Serializes as the backend's numeric `rate` and compares exactly, so it can be used to sort
+/// orders by price. Use `rate::exact` for a lossless serde form
+#[derive(Clone, Copy, Debug)]
+pub struct Rate {
+    numerator: U256,
+    denominator: U256,
+}
+
+impl Rate {
+    pub const ZERO: Rate =
+        Rate { numerator: U256::ZERO, denominator: U256::from_limbs([1, 0, 0, 0]) };
+
+    /// `numerator / denominator`, reduced. A zero denominator gives `Rate::ZERO`
+    pub fn from_ratio(numerator: U256, denominator: U256) -> Self {
+        Self::reduce(U512::from(numerator), U512::from(denominator))
+    }
+
+    /// Rate of `output_amount` for `input_amount`, both in base units, scaled by each token's
+    /// decimals so that 1 WETH (18) for 3000 USDC (6) is a rate of 3000
+    pub fn new(
+        input_amount: U256,
+        input_decimals: u8,
+        output_amount: U256,
+        output_decimals: u8,
+    ) -> Self {
+        let ten = U512::from(10);
+        Self::reduce(
+            U512::from(output_amount) * ten.pow(U512::from(input_decimals)),
+            U512::from(input_amount) * ten.pow(U512::from(output_decimals)),
+        )
+    }
+
+    /// Rate between raw base-unit amounts, ignoring decimals
+    pub fn from_amounts(input_amount: U256, output_amount: U256) -> Self {
+        Self::from_ratio(output_amount, input_amount)
+    }
+
+    pub fn for_order(order: &AoriOrder, input_decimals: u8, output_decimals: u8) -> Self {
+        Self::new(order.inputAmount, input_decimals, order.outputAmount, output_decimals)
+    }
+
+    pub fn numerator(&self) -> U256 {
+        self.numerator
+    }
+
+    pub fn denominator(&self) -> U256 {
+        self.denominator
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.numerator.is_zero()
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        f64::from(self.numerator) / f64::from(self.denominator)
+    }
+
+    /// Decimal representation rounded down to `decimals` fractional digits, without trailing
+    /// zeros
+    pub fn to_decimal_string(&self, decimals: usize) -> String {
+        let integer = self.numerator / self.denominator;
+        let mut remainder = U512::from(self.numerator % self.denominator);
+        let denominator = U512::from(self.denominator);
+
+        let mut fraction = String::with_capacity(decimals);
+        for _ in 0..decimals {
+            if remainder.is_zero() {
+                break;
+            }
+            remainder *= U512::from(10);
+            fraction.push(char::from(b'0' + (remainder / denominator).to::<u8>()));
+            remainder %= denominator;
+        }
+
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.is_empty() {
+            integer.to_string()
+        } else {
+            format!("{}.{}", integer, fraction)
+        }
+    }
+
+    /// Fractional digits of the exact decimal expansion, `None` if it doesn't terminate. A
+    /// denominator of 2^a * 5^b terminates after max(a, b) digits
+    fn decimal_places(&self) -> Option<usize> {
+        let twos = self.denominator.trailing_zeros();
+        let mut denominator = self.denominator >> twos;
+        let (five, mut fives) = (U256::from(5), 0);
+        while !denominator.is_zero() && (denominator % five).is_zero() {
+            denominator /= five;
+            fives += 1;
+        }
+        (denominator == U256::from(1)).then_some(twos.max(fives))
+    }
+
+    /// Reduces the fraction, dropping low bits in the rare case it still doesn't fit 256 bits
+    fn reduce(numerator: U512, denominator: U512) -> Self {
+        if denominator.is_zero() || numerator.is_zero() {
+            return Self::ZERO;
+        }
+
+        let gcd = numerator.gcd(denominator);
+        let (mut numerator, mut denominator) = (numerator / gcd, denominator / gcd);
+        let max = U512::from(U256::MAX);
+        while numerator > max || denominator > max {
+            numerator >>= 1;
+            denominator >>= 1;
+        }
+        if denominator.is_zero() {
+            denominator = U512::from(1);
+        }
+
+        Self { numerator: numerator.to(), denominator: denominator.to() }
+    }
+}
+
+impl Default for Rate {
+    fn default() -> Self {
+        Self::ZERO
+    }
+}
+
+impl Ord for Rate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let lhs = U512::from(self.numerator) * U512::from(other.denominator);
+        let rhs = U512::from(other.numerator) * U512::from(self.denominator);
+        lhs.cmp(&rhs)
+    }
+}
+
+impl PartialOrd for Rate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Rate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Rate {}
+
+impl fmt::Display for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_decimal_string(RATE_DISPLAY_DECIMALS))
+    }
+}
+
+/// Parses a plain decimal such as `"3000"` or `"0.000333"`, or a fraction such as `"2/3"`,
+/// exactly
+impl FromStr for Rate {
+    type Err = AoriError;
+
+    fn from_str(s: &str) -> AoriResult<Self> {
+        let invalid = || AoriError::Encoding(format!("Invalid rate: {}", s));
+
+        let s = s.trim();
+        if let Some((numerator, denominator)) = s.split_once('/') {
+            let parse = |n: &str| U256::from_str_radix(n.trim(), 10).map_err(|_| invalid());
+            let denominator = parse(denominator)?;
+            if denominator.is_zero() {
+                return Err(invalid());
+            }
+            return Ok(Self::from_ratio(parse(numerator)?, denominator));
+        }
+
+        let (integer, fraction) = s.split_once('.').unwrap_or((s, ""));
+        if integer.is_empty() && fraction.is_empty() ||
+            !integer.bytes().chain(fraction.bytes()).all(|b| b.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+
+        let digits = format!("{}{}", integer, fraction);
+        let numerator = U512::from_str_radix(&digits, 10).map_err(|_| invalid())?;
+        let denominator =
+            U512::from(10).checked_pow(U512::from(fraction.len())).ok_or_else(invalid)?;
+        Ok(Self::reduce(numerator, denominator))
+    }
+}
+
+impl Serialize for Rate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.to_f64())
+    }
+}
+
+/// Lossless serde form for `#[serde(with = "aori_rs::rate::exact")]`: a decimal string, or
+/// `"numerator/denominator"` when the decimal doesn't terminate
+pub mod exact {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(rate: &Rate, serializer: S) -> Result<S::Ok, S::Error> {
+        // longer decimals wouldn't parse back within `U512`
+        match rate.decimal_places() {
+            Some(places) if places <= MAX_EXACT_DECIMALS => {
+                serializer.serialize_str(&rate.to_decimal_string(places))
+            }
+            _ => serializer.collect_str(&format_args!("{}/{}", rate.numerator, rate.denominator)),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Rate, D::Error> {
+        Rate::deserialize(deserializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct RateVisitor;
+
+        impl<'de> de::Visitor<'de> for RateVisitor {
+            type Value = Rate;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a non-negative number or decimal string")
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Rate, E> {
+                Ok(Rate::from_ratio(U256::from(v), U256::from(1)))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Rate, E> {
+                u64::try_from(v).map_err(E::custom).and_then(|v| self.visit_u64(v))
+            }
+
+            // `f64`'s `Display` is the shortest decimal that round-trips, which is what the
+            // backend meant by the number
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<Rate, E> {
+                if !v.is_finite() || v < 0.0 {
+                    return Err(E::custom(format!("Invalid rate: {}", v)));
+                }
+                self.visit_str(&v.to_string())
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Rate, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(RateVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn units(amount: u64, decimals: u8) -> U256 {
+        U256::from(amount) * U256::from(10).pow(U256::from(decimals))
+    }
+
+    #[test]
+    fn scales_by_token_decimals() {
+        // 2 WETH for 6000 USDC
+        let rate = Rate::new(units(2, 18), 18, units(6000, 6), 6);
+        assert_eq!(rate, "3000".parse().unwrap());
+        assert_eq!(rate.to_string(), "3000");
+
+        let inverse = Rate::new(units(6000, 6), 6, units(2, 18), 18);
+        assert_eq!(inverse.to_decimal_string(6), "0.000333");
+
+        // integer division used to truncate this to 0
+        let raw = Rate::from_amounts(U256::from(3), U256::from(2));
+        assert_eq!(raw.to_string(), "0.666666666666666666");
+        assert!(!raw.is_zero());
+        assert_eq!(Rate::from_amounts(U256::ZERO, U256::from(2)), Rate::ZERO);
+    }
+
+    #[test]
+    fn sorts_exactly() {
+        let mut rates = [
+            Rate::from_ratio(U256::from(2), U256::from(3)),
+            Rate::from_ratio(U256::from(1), U256::from(2)),
+            Rate::from_ratio(U256::from(4), U256::from(6)),
+            Rate::from_ratio(U256::from(7), U256::from(10)),
+        ];
+        rates.sort();
+        assert_eq!(
+            rates.iter().map(|r| r.to_decimal_string(3)).collect::<Vec<_>>(),
+            vec!["0.5", "0.666", "0.666", "0.7"]
+        );
+        assert_eq!(rates[1], rates[2]);
+        assert!(rates[3] > rates[2]);
+    }
+
+    #[test]
+    fn serializes_like_the_backend() {
+        let rate: Rate = serde_json::from_str("1.25").unwrap();
+        assert_eq!(rate, Rate::from_ratio(U256::from(5), U256::from(4)));
+        assert_eq!(serde_json::to_string(&rate).unwrap(), "1.25");
+        let thirds = Rate::from_ratio(U256::from(2), U256::from(3));
+        assert!(serde_json::to_value(thirds).unwrap().is_f64());
+
+        assert_eq!(serde_json::from_str::<Rate>("3000").unwrap().to_string(), "3000");
+        assert_eq!(serde_json::from_str::<Rate>("\"0.0001\"").unwrap().to_string(), "0.0001");
+        assert!(serde_json::from_str::<Rate>("-1").is_err());
+        assert!("1.2.3".parse::<Rate>().is_err());
+    }
+
+    #[test]
+    fn exact_form_round_trips() {
+        #[derive(Serialize, Deserialize)]
+        struct Exact(#[serde(with = "exact")] Rate);
+
+        let json = |rate: Rate| serde_json::to_string(&Exact(rate)).unwrap();
+        let thirds = Rate::from_ratio(U256::from(2), U256::from(3));
+        assert_eq!(json(thirds), "\"2/3\"");
+        assert_eq!(json("1.25".parse().unwrap()), "\"1.25\"");
+
+        for (exponent, as_fraction) in [(40, false), (200, true)] {
+            let tiny = Rate::from_ratio(U256::from(1), U256::from(2).pow(U256::from(exponent)));
+            assert_eq!(json(tiny).contains('/'), as_fraction);
+            assert_eq!(serde_json::from_str::<Exact>(&json(tiny)).unwrap().0, tiny);
+        }
+        assert_eq!(serde_json::from_str::<Exact>(&json(thirds)).unwrap().0, thirds);
+        assert!("1/0".parse::<Rate>().is_err());
+    }
+}
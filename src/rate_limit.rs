@@ -0,0 +1,473 @@
+// client-side rate limiting
+// token buckets per endpoint and per method, with priority lanes so cancellations go first
+//
+// set through `HttpClientConfig::rate_limiter`, or `RateLimiter::layer` for other tower stacks.
+// One limiter can be shared by several clients, endpoints are told apart by host and port
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use hyper::{Body, Request};
+use jsonrpsee::http_client::transport::Error as TransportError;
+use tokio::time::{sleep, Instant};
+use tower::{Layer, Service};
+use tracing::warn;
+
+use super::{
+    error::{AoriError, AoriResult},
+    http_client::request_methods,
+};
+
+/// Requests allowed to wait on one bucket before normal and low priority requests are dropped
+pub const DEFAULT_MAX_QUEUED: usize = 256;
+
+/// `burst` requests at once, refilled at `per_second`. Built through the constructors, which
+/// reject quotas that would never admit a request
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quota {
+    pub burst: u32,
+    pub per_second: f64,
+}
+
+impl Quota {
+    pub fn new(burst: u32, per_second: f64) -> AoriResult<Self> {
+        if burst == 0 || !per_second.is_finite() || per_second <= 0.0 {
+            return Err(AoriError::Config(format!(
+                "invalid rate limit quota: burst {} at {} per second",
+                burst, per_second
+            )));
+        }
+        Ok(Self { burst, per_second })
+    }
+
+    pub fn per_second(per_second: u32) -> AoriResult<Self> {
+        Self::new(per_second, per_second as f64)
+    }
+
+    pub fn per_minute(per_minute: u32) -> AoriResult<Self> {
+        Self::new(per_minute, per_minute as f64 / 60.0)
+    }
+
+    pub fn burst(self, burst: u32) -> AoriResult<Self> {
+        Self::new(burst, self.per_second)
+    }
+}
+
+/// Waiting requests are served by lane, then in arrival order. High priority requests are
+/// never dropped
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    High,
+    Normal,
+    Low,
+}
+
+#[derive(Clone, Debug)]
+pub struct RateLimitConfig {
+    /// Applies to each endpoint without its own quota
+    pub endpoint: Option<Quota>,
+    /// Keyed by `host:port`
+    pub endpoints: HashMap<String, Quota>,
+    /// Keyed by JSON-RPC method, counted separately on each endpoint
+    pub methods: HashMap<String, Quota>,
+    /// Methods missing here are `Priority::Normal`
+    pub priorities: HashMap<String, Priority>,
+    pub max_queued: usize,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            endpoints: HashMap::new(),
+            methods: HashMap::new(),
+            priorities: HashMap::from([
+                ("aori_cancelOrder".to_string(), Priority::High),
+                ("aori_cancelAllOrders".to_string(), Priority::High),
+            ]),
+            max_queued: DEFAULT_MAX_QUEUED,
+        }
+    }
+}
+
+impl RateLimitConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn endpoint(mut self, quota: Quota) -> Self {
+        self.endpoint = Some(quota);
+        self
+    }
+
+    pub fn endpoint_quota(mut self, endpoint: impl Into<String>, quota: Quota) -> Self {
+        self.endpoints.insert(endpoint.into(), quota);
+        self
+    }
+
+    pub fn method(mut self, method: impl Into<String>, quota: Quota) -> Self {
+        self.methods.insert(method.into(), quota);
+        self
+    }
+
+    pub fn priority(mut self, method: impl Into<String>, priority: Priority) -> Self {
+        self.priorities.insert(method.into(), priority);
+        self
+    }
+
+    pub fn max_queued(mut self, max_queued: usize) -> Self {
+        self.max_queued = max_queued;
+        self
+    }
+}
+
+/// Counters for one method, or all of them added up
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RateLimitMetrics {
+    /// Sent without waiting
+    pub admitted: u64,
+    /// Sent after waiting for a token
+    pub queued: u64,
+    /// Refused because the queue was full
+    pub dropped: u64,
+    /// Waiting right now
+    pub waiting: u64,
+}
+
+impl RateLimitMetrics {
+    fn add(&mut self, other: &RateLimitMetrics) {
+        self.admitted += other.admitted;
+        self.queued += other.queued;
+        self.dropped += other.dropped;
+        self.waiting += other.waiting;
+    }
+}
+
+/// A request's place in a bucket's queue, ordered by lane then arrival
+type Ticket = (Priority, u64);
+
+struct Bucket {
+    quota: Quota,
+    tokens: f64,
+    refilled_at: Instant,
+    waiting: BTreeSet<Ticket>,
+    next_ticket: u64,
+}
+
+impl Bucket {
+    fn new(quota: Quota) -> Self {
+        Self {
+            quota,
+            tokens: quota.burst as f64,
+            refilled_at: Instant::now(),
+            waiting: BTreeSet::new(),
+            next_ticket: 0,
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.quota.per_second).min(self.quota.burst as f64);
+        self.refilled_at = now;
+    }
+
+    /// Takes a token for `ticket` once there are enough for everyone queued ahead of it,
+    /// otherwise returns how long until there will be
+    fn take(&mut self, ticket: &Ticket) -> Option<Duration> {
+        self.refill();
+        let needed = (self.waiting.range(..ticket).count() + 1) as f64;
+        if self.tokens >= needed {
+            self.tokens -= 1.0;
+            self.waiting.remove(ticket);
+            return None;
+        }
+
+        let refill = (needed - self.tokens) / self.quota.per_second.max(f64::MIN_POSITIVE);
+        Some(Duration::from_secs_f64(refill.min(60.0)).max(Duration::from_millis(1)))
+    }
+}
+
+/// Removes a ticket from its bucket when the request stops waiting, including when the
+/// request is cancelled
+struct Waiting<'a> {
+    limiter: &'a RateLimiter,
+    method: &'a str,
+    bucket: &'a Mutex<Bucket>,
+    ticket: Ticket,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.bucket.lock().unwrap().waiting.remove(&self.ticket);
+        self.limiter.record(self.method, |metrics| metrics.waiting -= 1);
+    }
+}
+
+#[derive(Default)]
+struct Limits {
+    buckets: HashMap<(String, Option<String>), Arc<Mutex<Bucket>>>,
+    metrics: HashMap<String, RateLimitMetrics>,
+}
+
+/// Shared token buckets, cheap to clone
+#[derive(Clone)]
+pub struct RateLimiter {
+    config: Arc<RateLimitConfig>,
+    limits: Arc<Mutex<Limits>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self { config: Arc::new(config), limits: Arc::default() }
+    }
+
+    pub fn config(&self) -> &RateLimitConfig {
+        &self.config
+    }
+
+    pub fn layer(&self) -> RateLimitLayer {
+        RateLimitLayer::new(Some(self.clone()))
+    }
+
+    pub fn priority(&self, method: &str) -> Priority {
+        self.config.priorities.get(method).copied().unwrap_or(Priority::Normal)
+    }
+
+    /// Waits until `method` may be sent to `endpoint`, or fails with `AoriError::RateLimited`
+    /// when its queue is full
+    pub async fn acquire(&self, endpoint: &str, method: &str) -> AoriResult<()> {
+        let priority = self.priority(method);
+        let endpoint_quota = self.config.endpoints.get(endpoint).copied().or(self.config.endpoint);
+        let method_quota = self.config.methods.get(method).copied();
+
+        let mut queued = false;
+        for (bucket_method, quota) in [(Some(method), method_quota), (None, endpoint_quota)] {
+            let Some(quota) = quota else { continue };
+            let bucket = self.bucket(endpoint, bucket_method, quota);
+            queued |= self.wait(&bucket, method, priority).await?;
+        }
+
+        self.record(method, |metrics| {
+            if queued {
+                metrics.queued += 1;
+            } else {
+                metrics.admitted += 1;
+            }
+        });
+        Ok(())
+    }
+
+    pub fn metrics(&self) -> RateLimitMetrics {
+        let limits = self.limits.lock().unwrap();
+        limits.metrics.values().fold(RateLimitMetrics::default(), |mut total, metrics| {
+            total.add(metrics);
+            total
+        })
+    }
+
+    pub fn method_metrics(&self, method: &str) -> RateLimitMetrics {
+        self.limits.lock().unwrap().metrics.get(method).copied().unwrap_or_default()
+    }
+
+    /// The bucket for `endpoint`, or for `method` on `endpoint`
+    fn bucket(&self, endpoint: &str, method: Option<&str>, quota: Quota) -> Arc<Mutex<Bucket>> {
+        let key = (endpoint.to_string(), method.map(String::from));
+        let mut limits = self.limits.lock().unwrap();
+        limits
+            .buckets
+            .entry(key)
+            .or_insert_with(|| Arc::new(Mutex::new(Bucket::new(quota))))
+            .clone()
+    }
+
+    /// Takes a token from `bucket`, returning whether the request had to wait for it
+    async fn wait(
+        &self,
+        bucket: &Mutex<Bucket>,
+        method: &str,
+        priority: Priority,
+    ) -> AoriResult<bool> {
+        let ticket = {
+            let mut bucket = bucket.lock().unwrap();
+            bucket.refill();
+            if bucket.waiting.is_empty() && bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+                return Ok(false);
+            }
+            if priority != Priority::High && bucket.waiting.len() >= self.config.max_queued {
+                drop(bucket);
+                self.record(method, |metrics| metrics.dropped += 1);
+                warn!("Rate limit queue full, dropping {}", method);
+                return Err(AoriError::RateLimited(format!("{} queue is full", method)));
+            }
+
+            let ticket = (priority, bucket.next_ticket);
+            bucket.next_ticket += 1;
+            bucket.waiting.insert(ticket);
+            ticket
+        };
+
+        self.record(method, |metrics| metrics.waiting += 1);
+        let _waiting = Waiting { limiter: self, method, bucket, ticket };
+        loop {
+            let wait = bucket.lock().unwrap().take(&ticket);
+            match wait {
+                Some(wait) => sleep(wait).await,
+                None => return Ok(true),
+            }
+        }
+    }
+
+    fn record(&self, method: &str, update: impl FnOnce(&mut RateLimitMetrics)) {
+        let mut limits = self.limits.lock().unwrap();
+        update(limits.metrics.entry(method.to_string()).or_default());
+    }
+}
+
+/// Tower layer holding requests back until the limiter lets them through. Without a limiter
+/// requests pass straight through
+#[derive(Clone, Default)]
+pub struct RateLimitLayer {
+    limiter: Option<RateLimiter>,
+}
+
+impl RateLimitLayer {
+    pub fn new(limiter: Option<RateLimiter>) -> Self {
+        Self { limiter }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService { inner, limiter: self.limiter.clone() }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitService<S> {
+    inner: S,
+    limiter: Option<RateLimiter>,
+}
+
+impl<S> Service<Request<Body>> for RateLimitService<S>
+where
+    S: Service<Request<Body>, Error = TransportError> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Response = S::Response;
+    type Error = TransportError;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, TransportError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), TransportError>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        // the clone may not be ready, so keep it and send on the one that is
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let Some(limiter) = self.limiter.clone() else {
+            return Box::pin(inner.call(request));
+        };
+
+        Box::pin(async move {
+            let (parts, body) = request.into_parts();
+            let body =
+                hyper::body::to_bytes(body).await.map_err(|e| TransportError::Http(Box::new(e)))?;
+            let endpoint = parts.uri.authority().map(|a| a.to_string()).unwrap_or_default();
+            for method in request_methods(&body) {
+                limiter
+                    .acquire(&endpoint, &method)
+                    .await
+                    .map_err(|e| TransportError::Http(Box::new(e)))?;
+            }
+            inner.call(Request::from_parts(parts, Body::from(body))).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        client::AoriBackendRpcClient, http_client::HttpClientConfig, test_utils::mock_server,
+        AoriPingParams, ProviderClient,
+    };
+    use jsonrpsee::server::RpcModule;
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn cancellations_jump_the_queue_and_full_queues_drop() {
+        let config = RateLimitConfig::new()
+            .endpoint(Quota::per_second(20).unwrap().burst(1).unwrap())
+            .max_queued(1);
+        let limiter = RateLimiter::new(config);
+        limiter.acquire("backend", "aori_makeOrder").await.unwrap();
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        for method in ["aori_makeOrder", "aori_cancelOrder"] {
+            let (limiter, sender) = (limiter.clone(), sender.clone());
+            tokio::spawn(async move {
+                limiter.acquire("backend", method).await.unwrap();
+                sender.send(method).unwrap();
+            });
+            tokio::task::yield_now().await;
+        }
+
+        // the make is already waiting, so there's no room for another
+        let dropped = limiter.acquire("backend", "aori_makeOrder").await;
+        assert!(matches!(dropped, Err(AoriError::RateLimited(_))));
+        // other endpoints have their own bucket
+        limiter.acquire("provider", "aori_makeOrder").await.unwrap();
+
+        assert_eq!(receiver.recv().await, Some("aori_cancelOrder"));
+        assert_eq!(receiver.recv().await, Some("aori_makeOrder"));
+        assert_eq!(
+            limiter.method_metrics("aori_makeOrder"),
+            RateLimitMetrics { admitted: 2, queued: 1, dropped: 1, waiting: 0 }
+        );
+        assert_eq!(limiter.metrics().queued, 2);
+    }
+
+    #[test]
+    fn rejects_quotas_that_never_admit() {
+        assert!(matches!(Quota::per_minute(0), Err(AoriError::Config(_))));
+        assert!(Quota::per_second(0).is_err());
+        assert!(Quota::per_second(5).unwrap().burst(0).is_err());
+        assert!(Quota::new(1, f64::NAN).is_err());
+        assert_eq!(Quota::per_minute(30).unwrap(), Quota { burst: 30, per_second: 0.5 });
+    }
+
+    #[tokio::test]
+    async fn layer_limits_http_clients() {
+        let mut module = RpcModule::new(());
+        module.register_method("aori_ping", |_, _| "aori_pong").unwrap();
+        let (url, handle) = mock_server(module).await;
+
+        let config =
+            RateLimitConfig::new().method("aori_ping", Quota::per_minute(1).unwrap()).max_queued(0);
+        let limiter = RateLimiter::new(config);
+        let provider = ProviderClient::new(vec![url.clone()]).with_rate_limiter(limiter.clone());
+
+        assert_eq!(provider.ping().await.unwrap(), "aori_pong");
+        assert!(matches!(provider.ping().await, Err(AoriError::RateLimited(_))));
+        assert_eq!(limiter.method_metrics("aori_ping").dropped, 1);
+
+        // backend clients share the limiter through their `HttpClientConfig`
+        let backend = HttpClientConfig::new().rate_limiter(limiter.clone()).build(&url).unwrap();
+        let ping = backend.ping(AoriPingParams::default()).await.map_err(AoriError::from_backend);
+        assert!(matches!(ping, Err(AoriError::RateLimited(_))));
+        assert_eq!(limiter.method_metrics("aori_ping").dropped, 2);
+
+        handle.stop().unwrap();
+    }
+}
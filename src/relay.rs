@@ -0,0 +1,256 @@
+// event relay
+// accepts `aori_broadcast` calls and fans the events out to websocket subscribers
+
+use std::{net::SocketAddr, sync::Arc};
+
+use alloy_primitives::{keccak256, B256};
+use jsonrpsee::{
+    core::{async_trait, RpcResult, SubscriptionResult},
+    proc_macros::rpc,
+    server::{PendingSubscriptionSink, Server, ServerHandle, SubscriptionMessage},
+    types::{ErrorObject, ErrorObjectOwned},
+};
+use serde::{Deserialize, Serialize};
+use tokio::{net::ToSocketAddrs, sync::broadcast};
+use tracing::warn;
+
+use super::{
+    error::{AoriError, AoriResult},
+    filter::FeedFilter,
+    subscription::SubscriptionEvents,
+    AoriBroadcastParams, AoriFeedEvents, Query,
+};
+
+pub const DEFAULT_RELAY_CAPACITY: usize = 1024;
+
+/// Which events a subscriber wants. Every set field must match; unset fields match anything
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayFilter {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<SubscriptionEvents>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain_ids: Option<Vec<u64>>,
+    /// Pairs match regardless of which side is the input token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pairs: Option<Vec<Query>>,
+    /// Anything the fields above can't express
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<FeedFilter>,
+}
+
+impl RelayFilter {
+    pub fn matches(&self, event: &AoriFeedEvents) -> bool {
+        if let Some(events) = &self.events {
+            if !events.contains(&SubscriptionEvents::from(event)) {
+                return false;
+            }
+        }
+
+        if let Some(chain_ids) = &self.chain_ids {
+            if !chain_ids.contains(&event.chain_id()) {
+                return false;
+            }
+        }
+
+        if let Some(pairs) = &self.pairs {
+            if !pairs.iter().any(|pair| FeedFilter::pair(pair.base, pair.quote).matches(event)) {
+                return false;
+            }
+        }
+
+        self.filter.as_ref().is_none_or(|filter| filter.matches(event))
+    }
+}
+
+impl From<FeedFilter> for RelayFilter {
+    fn from(filter: FeedFilter) -> Self {
+        Self { filter: Some(filter), ..Default::default() }
+    }
+}
+
+#[rpc(server, client)]
+pub trait AoriRelayRpc {
+    /// Publishes an event to every matching subscriber, returning how many subscriptions
+    /// were live when it was sent
+    #[method(name = "aori_broadcast")]
+    async fn broadcast(&self, parameters: AoriBroadcastParams) -> RpcResult<usize>;
+
+    #[subscription(
+        name = "aori_subscribe" => "aori_subscription",
+        unsubscribe = "aori_unsubscribe",
+        item = AoriFeedEvents
+    )]
+    async fn subscribe_feed(&self, filter: Option<RelayFilter>) -> SubscriptionResult;
+}
+
+/// In-process fanout for `AoriFeedEvents`, served over JSON-RPC (HTTP and WebSocket)
+#[derive(Clone)]
+pub struct AoriRelay {
+    secret_hash: B256,
+    sender: broadcast::Sender<Arc<AoriFeedEvents>>,
+}
+
+impl AoriRelay {
+    /// Fails on an empty `secret`, which would let anyone broadcast
+    pub fn new(secret: impl AsRef<str>) -> AoriResult<Self> {
+        Self::with_capacity(secret, DEFAULT_RELAY_CAPACITY)
+    }
+
+    /// `capacity` is how many events a slow subscriber may fall behind before it starts
+    /// missing events
+    pub fn with_capacity(secret: impl AsRef<str>, capacity: usize) -> AoriResult<Self> {
+        let secret = secret.as_ref();
+        if secret.is_empty() {
+            return Err(AoriError::Config("relay secret must not be empty".to_string()));
+        }
+        let (sender, _) = broadcast::channel(capacity);
+        Ok(Self { secret_hash: keccak256(secret), sender })
+    }
+
+    /// Publishes an event without going through `aori_broadcast`
+    pub fn publish(&self, event: AoriFeedEvents) -> usize {
+        self.sender.send(Arc::new(event)).unwrap_or(0)
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    /// Binds the relay and starts serving. The relay keeps running until the returned
+    /// handle is stopped
+    pub async fn start(self, addr: impl ToSocketAddrs) -> AoriResult<(SocketAddr, ServerHandle)> {
+        let server =
+            Server::builder().build(addr).await.map_err(|e| AoriError::Transport(e.to_string()))?;
+        let local_addr = server.local_addr().map_err(|e| AoriError::Transport(e.to_string()))?;
+        Ok((local_addr, server.start(self.into_rpc())))
+    }
+}
+
+// Messages mirror `AoriBackendErrors` so `Broadcaster` can map them back
+fn invalid_authorization() -> ErrorObjectOwned {
+    ErrorObject::owned(-32001, "Invalid Authorization", None::<()>)
+}
+
+fn incorrect_request_format() -> ErrorObjectOwned {
+    ErrorObject::owned(
+        -32602,
+        "Your JSON-RPC payload data doesn't fit into the correct format",
+        None::<()>,
+    )
+}
+
+#[async_trait]
+impl AoriRelayRpcServer for AoriRelay {
+    async fn broadcast(&self, parameters: AoriBroadcastParams) -> RpcResult<usize> {
+        // compared as hashes so the time taken doesn't tell how much of the secret matched
+        if keccak256(&parameters.secret) != self.secret_hash {
+            return Err(invalid_authorization());
+        }
+
+        let event: AoriFeedEvents =
+            serde_json::from_value(parameters.data).map_err(|_| incorrect_request_format())?;
+        Ok(self.publish(event))
+    }
+
+    async fn subscribe_feed(
+        &self,
+        pending: PendingSubscriptionSink,
+        filter: Option<RelayFilter>,
+    ) -> SubscriptionResult {
+        let filter = filter.unwrap_or_default();
+        let mut receiver = self.sender.subscribe();
+        let sink = pending.accept().await?;
+
+        loop {
+            tokio::select! {
+                _ = sink.closed() => break,
+                received = receiver.recv() => match received {
+                    Ok(event) => {
+                        if !filter.matches(&event) {
+                            continue;
+                        }
+                        let message = SubscriptionMessage::from_json(event.as_ref())?;
+                        if sink.send(message).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!(
+                            "Relay subscriber {:?} lagged, skipped {} events",
+                            sink.subscription_id(),
+                            skipped
+                        );
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::AoriBackendErrors, subscription::Broadcaster, QuoteRequestedData};
+    use alloy_primitives::Address;
+    use jsonrpsee::ws_client::WsClientBuilder;
+
+    fn quote(chain_id: u64) -> AoriFeedEvents {
+        AoriFeedEvents::QuoteRequested(Box::new(QuoteRequestedData {
+            input_token: Address::repeat_byte(2).to_string(),
+            output_token: Address::repeat_byte(4).to_string(),
+            input_amount: "1000000000000000000".to_string(),
+            chain_id,
+        }))
+    }
+
+    #[test]
+    fn filter_matches_pairs_in_either_direction() {
+        let filter = RelayFilter {
+            events: Some(vec![SubscriptionEvents::QuoteRequested]),
+            chain_ids: Some(vec![42161]),
+            pairs: Some(vec![Query {
+                base: Address::repeat_byte(4),
+                quote: Address::repeat_byte(2),
+            }]),
+            filter: None,
+        };
+
+        assert!(filter.matches(&quote(42161)));
+        assert!(!filter.matches(&quote(1)));
+        assert!(RelayFilter::default().matches(&quote(1)));
+
+        let dsl = RelayFilter::from(FeedFilter::ChainId(1).negate());
+        assert!(!dsl.matches(&quote(1)));
+        assert!(RelayFilter { chain_ids: Some(vec![42161]), ..dsl }.matches(&quote(42161)));
+    }
+
+    #[tokio::test]
+    async fn broadcasts_reach_matching_subscribers() {
+        assert!(AoriRelay::new("").is_err());
+        let relay = AoriRelay::new("hunter2").unwrap();
+        let (addr, handle) = relay.start("127.0.0.1:0").await.unwrap();
+
+        let client = WsClientBuilder::default().build(format!("ws://{}", addr)).await.unwrap();
+        let filter = RelayFilter { chain_ids: Some(vec![42161]), ..Default::default() };
+        let mut subscription = client.subscribe_feed(Some(filter)).await.unwrap();
+
+        let broadcaster = Broadcaster::new(format!("http://{}", addr), "hunter2");
+        let results = broadcaster.broadcast_batch(&[quote(1), quote(42161)]).await.unwrap();
+        assert!(results.iter().all(|result| result.is_ok()));
+
+        let received = subscription.next().await.unwrap().unwrap();
+        assert_eq!(received.chain_id(), 42161);
+
+        let wrong_secret = Broadcaster::new(format!("http://{}", addr), "hunter3");
+        assert!(matches!(
+            wrong_secret.broadcast(&quote(42161)).await,
+            Err(AoriError::Backend(AoriBackendErrors::InvalidAuthorization()))
+        ));
+
+        handle.stop().unwrap();
+    }
+}
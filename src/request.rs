@@ -1,8 +1,9 @@
-use crate::shared_types::AoriOrder;
-use alloy_serde_macro::{bytes_as_string, bytes_from_string};
+use crate::{
+    error::{AoriError, AoriResult},
+    shared_types::AoriOrder,
+};
 use serde::{Deserialize, Serialize};
-
-use super::{OrderView, Query, ViewOrderbookQuery};
+use serde_json::Value;
 
 #[derive(Clone, Default, Serialize, Deserialize, Debug)]
 pub struct AoriPingParams(String);
@@ -135,7 +136,6 @@ pub struct AoriViewOrderbookParams {
 
 ///
 ///  Response Types
-///
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct AoriPingRequest {
@@ -223,18 +223,70 @@ pub struct AoriBroadcastRequest {
     pub params: Vec<AoriBroadcastParams>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub enum AoriRequest {
-    Ping(AoriPingRequest),
-    AccountBalance(AoriAccountBalanceRequest),
-    AccountOrders(AoriAccountOrdersRequest),
-    RequestQuote(AoriRequestQuoteRequest),
-    MakeOrder(AoriMakeOrderRequest),
-    TakeOrder(AoriTakeOrderRequest),
-    CancelOrder(AoriCancelOrderRequest),
-    CancelAllOrders(AoriCancelAllOrdersRequest),
-    ViewOrderbook(AoriViewOrderbookRequest),
+/// The one table of methods `AoriRequest` and `AoriResponse` have a variant for. Passes
+/// `Variant => "method", Request, Response;` for each to `$callback`, so the method list, both
+/// enums and their dispatch are all generated from it
+macro_rules! aori_methods {
+    ($callback:ident) => {
+        $callback! {
+            Ping => "aori_ping",
+                AoriPingRequest, AoriPingResponse;
+            AccountBalance => "aori_accountBalance",
+                AoriAccountBalanceRequest, AoriAccountBalanceResponse;
+            AccountOrders => "aori_accountOrders",
+                AoriAccountOrdersRequest, AoriAccountOrdersResponse;
+            RequestQuote => "aori_requestQuote",
+                AoriRequestQuoteRequest, AoriRequestQuoteResponse;
+            MakeOrder => "aori_makeOrder",
+                AoriMakeOrderRequest, AoriMakeOrderResponse;
+            TakeOrder => "aori_takeOrder",
+                AoriTakeOrderRequest, Box<AoriTakeOrderResponse>;
+            CancelOrder => "aori_cancelOrder",
+                AoriCancelOrderRequest, AoriCancelOrderResponse;
+            CancelAllOrders => "aori_cancelAllOrders",
+                AoriCancelAllOrdersRequest, AoriCancelAllOrdersResponse;
+            ViewOrderbook => "aori_viewOrderbook",
+                AoriViewOrderbookRequest, AoriViewOrderbookResponse;
+        }
+    };
+}
+pub(crate) use aori_methods;
+
+macro_rules! method_names {
+    ($($variant:ident => $method:literal, $request:ident, $response:ty;)*) => {
+        &[$($method),*]
+    };
+}
+
+/// Methods `AoriRequest` has a variant for
+pub const AORI_REQUEST_METHODS: &[&str] = aori_methods!(method_names);
+
+macro_rules! request_enum {
+    ($($variant:ident => $method:literal, $request:ident, $response:ty;)*) => {
+        /// Serializes as the inner JSON-RPC envelope and deserializes through `AoriRequestin`,
+        /// so it reads and writes wire JSON-RPC
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(untagged, try_from = "AoriRequestin")]
+        pub enum AoriRequest {
+            $($variant($request),)*
+        }
+
+        impl AoriRequest {
+            pub fn id(&self) -> i64 {
+                match self {
+                    $(AoriRequest::$variant(request) => request.id,)*
+                }
+            }
+
+            pub fn method(&self) -> &str {
+                match self {
+                    $(AoriRequest::$variant(request) => &request.method,)*
+                }
+            }
+        }
+    };
 }
+aori_methods!(request_enum);
 
 // TODO: rename from AoriRequestin to AoriGeneralRequest
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -244,3 +296,92 @@ pub struct AoriRequestin {
     pub method: String,
     pub params: serde_json::Value,
 }
+
+impl AoriRequestin {
+    /// JSON-RPC 2.0 envelope passing `params` as the only positional parameter
+    pub fn new(id: i64, method: impl Into<String>, params: impl Serialize) -> AoriResult<Self> {
+        Ok(Self {
+            id,
+            jsonrpc: "2.0".to_string(),
+            method: method.into(),
+            params: Value::Array(vec![serde_json::to_value(params)?]),
+        })
+    }
+}
+
+/// Routes a generic envelope to its typed request by `method`. `params` may be the usual
+/// positional array or a single by-name object
+impl TryFrom<AoriRequestin> for AoriRequest {
+    type Error = AoriError;
+
+    fn try_from(request: AoriRequestin) -> AoriResult<Self> {
+        let AoriRequestin { id, jsonrpc, method, params } = request;
+        let params = match params {
+            Value::Object(_) => Value::Array(vec![params]),
+            Value::Null => Value::Array(vec![]),
+            params => params,
+        };
+
+        macro_rules! typed {
+            ($variant:ident, $request:ident) => {
+                AoriRequest::$variant($request {
+                    id,
+                    jsonrpc,
+                    params: serde_json::from_value(params).map_err(|e| {
+                        AoriError::Encoding(format!("Invalid params for {}: {}", method, e))
+                    })?,
+                    method,
+                })
+            };
+        }
+
+        macro_rules! dispatch {
+            ($($variant:ident => $method:literal, $request:ident, $response:ty;)*) => {
+                match method.as_str() {
+                    $($method => typed!($variant, $request),)*
+                    _ => return Err(AoriError::Encoding(format!("Unknown method {}", method))),
+                }
+            };
+        }
+
+        Ok(aori_methods!(dispatch))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn envelope(method: &str, params: Value) -> AoriRequestin {
+        AoriRequestin { id: 7, jsonrpc: "2.0".to_string(), method: method.to_string(), params }
+    }
+
+    #[test]
+    fn dispatches_envelopes_by_method() {
+        let request: AoriRequest =
+            envelope("aori_cancelOrder", json!([{ "orderHash": "0x01", "apiKey": "key" }]))
+                .try_into()
+                .unwrap();
+        assert_eq!(request.id(), 7);
+        assert_eq!(request.method(), "aori_cancelOrder");
+        let AoriRequest::CancelOrder(cancel) = request else {
+            panic!("expected a CancelOrder request");
+        };
+        assert_eq!(cancel.params[0].api_key, "key");
+
+        let request: AoriRequest =
+            envelope("aori_cancelAllOrders", json!({ "apiKey": "key", "tag": "bot" }))
+                .try_into()
+                .unwrap();
+        assert!(matches!(request, AoriRequest::CancelAllOrders(r) if r.params[0].tag.is_some()));
+
+        let request: AoriRequest = envelope("aori_ping", Value::Null).try_into().unwrap();
+        assert!(matches!(request, AoriRequest::Ping(_)));
+
+        let unknown = AoriRequest::try_from(envelope("aori_unknown", json!([])));
+        assert!(unknown.unwrap_err().to_string().contains("Unknown method"));
+        let invalid = AoriRequest::try_from(envelope("aori_cancelOrder", json!([{}])));
+        assert!(invalid.unwrap_err().to_string().contains("Invalid params"));
+    }
+}
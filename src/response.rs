@@ -1,9 +1,8 @@
-use crate::{shared_types::AoriOrder, SettledMatch};
-use alloy_primitives::U256;
-use alloy_serde_macro::U256_from_u64;
+use crate::SettledMatch;
+use alloy_primitives::{Address, U256};
 use serde::{Deserialize, Serialize};
 
-use super::{DetailsToExecute, OrderView};
+use super::{AoriMatchingDetails, DetailsToExecute, OrderView};
 
 #[derive(Default, Serialize, Deserialize, Debug)]
 pub struct AoriPingResponse {
@@ -94,13 +93,98 @@ pub struct AoriFeedEventWrapper {
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(tag = "type", content = "data")]
 pub enum AoriFeedEvents {
-    OrderToExecute(Box<OrderToExecuteData>),
+    OrderToExecute(Box<DetailsToExecute>),
     SwapRequested(Box<OrderView>),
     QuoteRequested(Box<QuoteRequestedData>),
     OrderCreated(Box<OrderView>),
     OrderTaken(Box<OrderView>),
     OrderCancelled(Box<OrderView>),
-    OrderFulfilled(Box<SettledMatch>)
+    OrderFulfilled(Box<SettledMatch>),
+}
+
+impl AoriFeedEvents {
+    /// Chain the event belongs to, using the input side for orders and matches
+    pub fn chain_id(&self) -> u64 {
+        match self {
+            AoriFeedEvents::OrderToExecute(data) => data.chain_id.saturating_to(),
+            AoriFeedEvents::QuoteRequested(data) => data.chain_id,
+            AoriFeedEvents::SwapRequested(order) |
+            AoriFeedEvents::OrderCreated(order) |
+            AoriFeedEvents::OrderTaken(order) |
+            AoriFeedEvents::OrderCancelled(order) => order.input_chain_id.saturating_to(),
+            AoriFeedEvents::OrderFulfilled(settled) => settled.input_chain_id.saturating_to(),
+        }
+    }
+
+    /// Input and output token of the order, quote or match behind the event
+    pub fn tokens(&self) -> Option<(Address, Address)> {
+        match self {
+            AoriFeedEvents::OrderToExecute(data) => Some((data.input_token, data.output_token)),
+            AoriFeedEvents::QuoteRequested(data) => {
+                Some((data.input_token.parse().ok()?, data.output_token.parse().ok()?))
+            }
+            AoriFeedEvents::SwapRequested(order) |
+            AoriFeedEvents::OrderCreated(order) |
+            AoriFeedEvents::OrderTaken(order) |
+            AoriFeedEvents::OrderCancelled(order) => Some((order.input_token, order.output_token)),
+            AoriFeedEvents::OrderFulfilled(settled) => {
+                Some((settled.input_token, settled.output_token))
+            }
+        }
+    }
+
+    /// Offerer of the order behind an order lifecycle event
+    pub fn offerer(&self) -> Option<Address> {
+        match self {
+            AoriFeedEvents::SwapRequested(order) |
+            AoriFeedEvents::OrderCreated(order) |
+            AoriFeedEvents::OrderTaken(order) |
+            AoriFeedEvents::OrderCancelled(order) => Some(order.offerer),
+            _ => None,
+        }
+    }
+
+    /// Maker of a matched or settled pair of orders
+    pub fn maker(&self) -> Option<Address> {
+        match self {
+            AoriFeedEvents::OrderToExecute(data) => Some(data.maker),
+            AoriFeedEvents::OrderFulfilled(settled) => Some(settled.maker),
+            _ => None,
+        }
+    }
+
+    /// Taker of a matched or settled pair of orders
+    pub fn taker(&self) -> Option<Address> {
+        match self {
+            AoriFeedEvents::OrderToExecute(data) => Some(data.taker),
+            AoriFeedEvents::OrderFulfilled(settled) => Some(settled.taker),
+            _ => None,
+        }
+    }
+
+    pub fn input_amount(&self) -> Option<U256> {
+        match self {
+            AoriFeedEvents::OrderToExecute(data) => Some(data.input_amount),
+            AoriFeedEvents::QuoteRequested(data) => data.input_amount.parse().ok(),
+            AoriFeedEvents::SwapRequested(order) |
+            AoriFeedEvents::OrderCreated(order) |
+            AoriFeedEvents::OrderTaken(order) |
+            AoriFeedEvents::OrderCancelled(order) => Some(order.input_amount),
+            AoriFeedEvents::OrderFulfilled(settled) => Some(settled.input_amount),
+        }
+    }
+
+    pub fn output_amount(&self) -> Option<U256> {
+        match self {
+            AoriFeedEvents::OrderToExecute(data) => Some(data.output_amount),
+            AoriFeedEvents::QuoteRequested(_) => None,
+            AoriFeedEvents::SwapRequested(order) |
+            AoriFeedEvents::OrderCreated(order) |
+            AoriFeedEvents::OrderTaken(order) |
+            AoriFeedEvents::OrderCancelled(order) => Some(order.output_amount),
+            AoriFeedEvents::OrderFulfilled(settled) => Some(settled.output_amount),
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
@@ -112,42 +196,14 @@ pub struct QuoteRequestedData {
     pub chain_id: u64,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct MatchingOrder {
-    pub maker_order: AoriOrder,
-    pub taker_order: AoriOrder,
-    pub maker_signature: String,
-    pub taker_signature: String,
-    pub block_deadline: u64,
-    pub seat_number: u64,
-    pub seat_holder: String,
-    pub seat_percent_of_fees: u64,
-}
-
-#[derive(Debug, Clone, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct OrderToExecuteData {
-    pub matching_hash: String,
-    pub matching: MatchingOrder,
-    pub matching_signature: String,
-    pub maker_order_hash: String,
-    pub maker_chain_id: u64,
-    pub maker_zone: String,
-    pub taker_order_hash: String,
-    pub taker_chain_id: u64,
-    pub taker_zone: String,
-    pub chain_id: u64,
-    pub to: String,
-    pub value: U256,
-    pub data: String,
-    pub maker: String,
-    pub taker: String,
-    pub input_token: String,
-    pub input_amount: String,
-    pub output_token: String,
-    pub output_amount: String,
-}
+/// `matching` payload of an `OrderToExecute` event, the same struct passed to `settleOrders`
+#[deprecated(note = "use `AoriMatchingDetails`")]
+pub type MatchingOrder = AoriMatchingDetails;
+
+/// `OrderToExecute` payload, deserialized straight into the typed `DetailsToExecute` so the
+/// feed and locally built details can't drift apart
+#[deprecated(note = "use `DetailsToExecute`")]
+pub type OrderToExecuteData = DetailsToExecute;
 
 pub fn deserialize_aori_feed_event(json_data: &str) -> Result<AoriFeedEvents, serde_json::Error> {
     let wrapper = serde_json::from_str::<AoriFeedEventWrapper>(json_data)?;
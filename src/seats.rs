@@ -0,0 +1,236 @@
+// seats
+// looks up Aori seats and picks the one an order should be routed through
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use alloy_primitives::Address;
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+use super::{
+    batch::MAX_BATCH_SIZE,
+    constants::{DEFAULT_SEAT_HOLDER, DEFAULT_SEAT_ID, DEFAULT_SEAT_SCORE},
+    error::{AoriError, AoriResult},
+    provider::ProviderClient,
+};
+
+/// How long the scanned seat table is reused before the provider is asked again
+pub const DEFAULT_SEAT_CACHE_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeatDetails {
+    #[serde(alias = "seatNumber", deserialize_with = "deserialize_u64")]
+    pub seat_id: u64,
+    #[serde(rename = "seatHolder")]
+    pub holder: Address,
+    #[serde(rename = "seatScore", deserialize_with = "deserialize_u64")]
+    pub score: u64,
+    #[serde(rename = "seatPercentOfFees", default, deserialize_with = "deserialize_u64")]
+    pub percent_of_fees: u64,
+    /// Owner of the seat NFT, which may delegate the seat to a different holder
+    #[serde(rename = "seatOwner", default)]
+    pub owner: Address,
+}
+
+impl SeatDetails {
+    /// The seat orders go through when the signer holds none
+    pub fn default_seat() -> Self {
+        let holder = DEFAULT_SEAT_HOLDER.parse().unwrap_or_default();
+        Self {
+            seat_id: DEFAULT_SEAT_ID as u64,
+            holder,
+            score: DEFAULT_SEAT_SCORE as u64,
+            percent_of_fees: 0,
+            owner: holder,
+        }
+    }
+
+    pub fn is_held_by(&self, address: Address) -> bool {
+        self.holder == address || self.owner == address
+    }
+}
+
+/// Accepts both JSON numbers and numeric strings, as the provider returns either
+fn deserialize_u64<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum NumberOrString {
+        Number(u64),
+        String(String),
+    }
+
+    match NumberOrString::deserialize(deserializer)? {
+        NumberOrString::Number(n) => Ok(n),
+        NumberOrString::String(s) => s.parse().map_err(de::Error::custom),
+    }
+}
+
+/// Picks the highest scoring seat, preferring the lower id on ties
+pub fn choose_seat(seats: &[SeatDetails]) -> Option<&SeatDetails> {
+    seats.iter().max_by(|a, b| a.score.cmp(&b.score).then(b.seat_id.cmp(&a.seat_id)))
+}
+
+pub struct Seats {
+    provider: ProviderClient,
+    cache_ttl: Duration,
+    cache: Mutex<Option<(Instant, Vec<SeatDetails>)>>,
+}
+
+impl Seats {
+    pub fn new(provider: ProviderClient) -> Self {
+        Self { provider, cache_ttl: DEFAULT_SEAT_CACHE_TTL, cache: Mutex::new(None) }
+    }
+
+    pub fn with_cache_ttl(mut self, cache_ttl: Duration) -> Self {
+        self.cache_ttl = cache_ttl;
+        self
+    }
+
+    pub async fn details(&self, seat_id: u64) -> AoriResult<SeatDetails> {
+        self.provider.get_seat_details(seat_id).await
+    }
+
+    /// Every minted seat, cached for the configured ttl. Seat ids are minted in sequence, so
+    /// ids are looked up a batch at a time until a batch finds none; ids the provider rejects
+    /// are treated as unminted and transport failures abort the scan
+    pub async fn all(&self) -> AoriResult<Vec<SeatDetails>> {
+        if let Some((scanned_at, seats)) = &*self.cache.lock().unwrap() {
+            if scanned_at.elapsed() < self.cache_ttl {
+                return Ok(seats.clone());
+            }
+        }
+
+        let mut seats = Vec::new();
+        for page in 0.. {
+            let start = page * MAX_BATCH_SIZE as u64;
+            let lookups =
+                self.provider.get_seats_details(start..start + MAX_BATCH_SIZE as u64).await?;
+
+            let minted = seats.len();
+            for lookup in lookups {
+                match lookup {
+                    Ok(seat) => seats.push(seat),
+                    Err(AoriError::Provider(_)) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            if seats.len() == minted {
+                break;
+            }
+        }
+
+        *self.cache.lock().unwrap() = Some((Instant::now(), seats.clone()));
+        Ok(seats)
+    }
+
+    /// Seats held or owned by `address`, by id
+    pub async fn owned_by(&self, address: Address) -> AoriResult<Vec<SeatDetails>> {
+        let mut seats: Vec<SeatDetails> =
+            self.all().await?.into_iter().filter(|seat| seat.is_held_by(address)).collect();
+        seats.sort_by_key(|seat| seat.seat_id);
+        Ok(seats)
+    }
+
+    /// Best seat held by `address`, falling back to the default seat
+    pub async fn seat_for(&self, address: Address) -> AoriResult<SeatDetails> {
+        let seats = self.owned_by(address).await?;
+        Ok(choose_seat(&seats).cloned().unwrap_or_else(SeatDetails::default_seat))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{provider::AoriGetSeatDetailsParams, test_utils::mock_server};
+    use jsonrpsee::{
+        server::{RpcModule, ServerHandle},
+        types::ErrorObject,
+    };
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    const HOLDER: Address = Address::repeat_byte(7);
+
+    /// Seats 0 to `minted` exist, every tenth held by `HOLDER`; counts the lookups it serves
+    async fn seats_server(minted: u64) -> (String, ServerHandle, Arc<AtomicUsize>) {
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let mut module = RpcModule::new(lookups.clone());
+        module
+            .register_method("aori_getSeatDetails", move |params, lookups| {
+                lookups.fetch_add(1, Ordering::SeqCst);
+                let (params,): (AoriGetSeatDetailsParams,) = params.parse()?;
+                if params.seat_id > minted {
+                    return Err(ErrorObject::owned(-32000, "Seat does not exist", None::<()>));
+                }
+                let holder = if params.seat_id % 10 == 0 { HOLDER } else { Address::ZERO };
+                Ok(serde_json::json!({
+                    "id": 1,
+                    "result": {
+                        "seatId": params.seat_id,
+                        "seatHolder": holder,
+                        "seatScore": params.seat_id,
+                    }
+                }))
+            })
+            .unwrap();
+
+        let (url, handle) = mock_server(module).await;
+        (url, handle, lookups)
+    }
+
+    #[test]
+    fn seat_details_parse_numbers_and_strings() {
+        let seat: SeatDetails = serde_json::from_str(
+            r#"{
+                "seatId": "3",
+                "seatHolder": "0x2EDEB6E06E81020F48d930FA7444a592ebE9FaB6",
+                "seatScore": 4,
+                "seatPercentOfFees": "20"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(seat.seat_id, 3);
+        assert_eq!(seat.score, 4);
+        assert_eq!(seat.percent_of_fees, 20);
+        assert_eq!(seat.owner, Address::ZERO);
+        assert!(seat.is_held_by(SeatDetails::default_seat().holder));
+    }
+
+    #[test]
+    fn chooses_highest_scoring_seat() {
+        let seat = |seat_id, score| SeatDetails { seat_id, score, ..SeatDetails::default_seat() };
+        let seats = vec![seat(7, 2), seat(4, 5), seat(9, 5), seat(1, 1)];
+
+        assert_eq!(choose_seat(&seats).unwrap().seat_id, 4);
+        assert!(choose_seat(&[]).is_none());
+    }
+
+    #[tokio::test]
+    async fn scans_every_minted_seat_and_caches_them() {
+        let minted = 2 * MAX_BATCH_SIZE as u64 + 30;
+        let (url, handle, lookups) = seats_server(minted).await;
+        let seats = Seats::new(ProviderClient::new(vec![url]));
+
+        let owned = seats.owned_by(HOLDER).await.unwrap();
+        let ids: Vec<u64> = owned.iter().map(|seat| seat.seat_id).collect();
+        assert_eq!(ids, (0..=minted).step_by(10).collect::<Vec<_>>());
+        assert_eq!(seats.seat_for(HOLDER).await.unwrap().seat_id, 230);
+        // three batches with seats, then one that found none
+        assert_eq!(lookups.load(Ordering::SeqCst), 4 * MAX_BATCH_SIZE);
+
+        let stranger = seats.seat_for(Address::repeat_byte(1)).await.unwrap();
+        assert_eq!(stranger, SeatDetails::default_seat());
+        assert_eq!(lookups.load(Ordering::SeqCst), 4 * MAX_BATCH_SIZE);
+
+        handle.stop().unwrap();
+    }
+}
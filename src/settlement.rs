@@ -0,0 +1,554 @@
+// settlement
+// reads `OrdersSettled` logs straight from the zone contracts to reconcile fills
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use alloy_primitives::{Address, Bytes, B256, U256};
+use alloy_sol_types::SolEvent;
+use jsonrpsee::proc_macros::rpc;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    task::JoinHandle,
+    time::{sleep, Instant},
+};
+
+use super::{
+    chains::ChainId,
+    contract::{parse_quantity, ContractCall},
+    error::{AoriError, AoriResult},
+    http_client::{AoriHttpClient, HttpClientConfig},
+    provider::ProviderClient,
+    AoriV2::OrdersSettled,
+    SettledMatch,
+};
+
+/// Largest block range requested from a node in one `eth_getLogs` call
+pub const MAX_LOG_BLOCK_RANGE: u64 = 2_000;
+
+/// Log as returned by `eth_getLogs`. `blockTimestamp` is only set by some nodes
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AoriLog {
+    pub address: Address,
+    pub topics: Vec<B256>,
+    pub data: Bytes,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction_hash: Option<B256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_number: Option<U256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_timestamp: Option<U256>,
+}
+
+/// Topic0 of `OrdersSettled`
+pub fn orders_settled_topic() -> B256 {
+    OrdersSettled::SIGNATURE_HASH
+}
+
+/// Decodes an `OrdersSettled` event from its raw topics and data. Transaction details are
+/// left empty
+pub fn decode_orders_settled(topics: &[B256], data: &[u8]) -> AoriResult<SettledMatch> {
+    let event = OrdersSettled::decode_raw_log(topics.iter().copied(), data, true)
+        .map_err(|e| AoriError::Encoding(e.to_string()))?;
+
+    Ok(SettledMatch {
+        maker_order_hash: event.makerHash,
+        taker_order_hash: event.takerHash,
+        maker: event.maker,
+        taker: event.taker,
+        input_chain_id: event.inputChainId,
+        output_chain_id: event.outputChainId,
+        input_zone: event.inputZone,
+        output_zone: event.outputZone,
+        input_token: event.inputToken,
+        output_token: event.outputToken,
+        input_amount: event.inputAmount,
+        output_amount: event.outputAmount,
+        matching_hash: event.matchingHash,
+        transaction_hash: None,
+        block_number: None,
+        timestamp: None,
+    })
+}
+
+impl TryFrom<&AoriLog> for SettledMatch {
+    type Error = AoriError;
+
+    fn try_from(log: &AoriLog) -> Result<Self, Self::Error> {
+        Ok(SettledMatch {
+            transaction_hash: log.transaction_hash,
+            block_number: log.block_number,
+            timestamp: log.block_timestamp,
+            ..decode_orders_settled(&log.topics, &log.data)?
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LogFilter {
+    address: Address,
+    from_block: U256,
+    to_block: U256,
+    topics: Vec<B256>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct BlockHeader {
+    timestamp: U256,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct CallRequest {
+    from: Address,
+    to: Address,
+    data: Bytes,
+    value: U256,
+}
+
+// the data provider doesn't serve logs or calls, so they're read from a node of the chain directly
+#[rpc(client)]
+trait EthNodeRpc {
+    #[method(name = "eth_getLogs")]
+    async fn get_logs(&self, filter: LogFilter) -> RpcResult<Vec<AoriLog>>;
+    #[method(name = "eth_getBlockByNumber")]
+    async fn get_block_by_number(&self, block: U256, full: bool) -> RpcResult<Option<BlockHeader>>;
+    #[method(name = "eth_call")]
+    async fn call(&self, request: CallRequest, block: &str) -> RpcResult<Bytes>;
+}
+
+/// JSON-RPC node of a single chain, for the standard `eth_` calls the data provider lacks
+#[derive(Clone)]
+pub struct ChainNode {
+    client: Arc<AoriHttpClient>,
+}
+
+impl ChainNode {
+    pub fn new(url: &str) -> AoriResult<Self> {
+        Self::with_http_config(url, &HttpClientConfig::default())
+    }
+
+    pub fn with_http_config(url: &str, http: &HttpClientConfig) -> AoriResult<Self> {
+        Ok(Self { client: Arc::new(http.build(url)?) })
+    }
+
+    pub async fn get_logs(
+        &self,
+        address: Address,
+        from_block: u64,
+        to_block: u64,
+        topics: Vec<B256>,
+    ) -> AoriResult<Vec<AoriLog>> {
+        let filter = LogFilter {
+            address,
+            from_block: U256::from(from_block),
+            to_block: U256::from(to_block),
+            topics,
+        };
+        self.client.get_logs(filter).await.map_err(AoriError::from_provider)
+    }
+
+    pub async fn block_timestamp(&self, block_number: U256) -> AoriResult<U256> {
+        self.client
+            .get_block_by_number(block_number, false)
+            .await
+            .map_err(AoriError::from_provider)?
+            .map(|block| block.timestamp)
+            .ok_or_else(|| AoriError::Provider(format!("Block {} not found", block_number)))
+    }
+
+    /// `eth_call`s `call` from `from` against the latest block and returns the raw return data
+    pub async fn call(&self, call: &ContractCall, from: Address) -> AoriResult<Bytes> {
+        let request = CallRequest { from, to: call.to, data: call.data.clone(), value: call.value };
+        self.client.call(request, "latest").await.map_err(AoriError::from_provider)
+    }
+}
+
+/// Fetches every `OrdersSettled` log emitted by `zone` between `from_block` and `to_block`
+/// (inclusive), splitting the range into `MAX_LOG_BLOCK_RANGE` sized requests. Timestamps
+/// the node leaves out are looked up from the logs' blocks
+pub async fn scan_orders_settled(
+    node: &ChainNode,
+    zone: Address,
+    from_block: u64,
+    to_block: u64,
+) -> AoriResult<Vec<SettledMatch>> {
+    let mut settled = Vec::new();
+    let mut timestamps = HashMap::new();
+    let mut start = from_block;
+
+    while start <= to_block {
+        let end = to_block.min(start.saturating_add(MAX_LOG_BLOCK_RANGE - 1));
+        let logs = node.get_logs(zone, start, end, vec![orders_settled_topic()]).await?;
+
+        for log in logs.iter().filter(|log| log.topics.first() == Some(&orders_settled_topic())) {
+            let mut settled_match = SettledMatch::try_from(log)?;
+            if let (None, Some(block)) = (settled_match.timestamp, settled_match.block_number) {
+                let timestamp = match timestamps.get(&block) {
+                    Some(timestamp) => *timestamp,
+                    None => {
+                        let timestamp = node.block_timestamp(block).await?;
+                        timestamps.insert(block, timestamp);
+                        timestamp
+                    }
+                };
+                settled_match.timestamp = Some(timestamp);
+            }
+            settled.push(settled_match);
+        }
+
+        if end == u64::MAX {
+            break;
+        }
+        start = end + 1;
+    }
+
+    Ok(settled)
+}
+
+////////////////////////////////////////////////////////////////
+//                          TRACKER
+////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Debug)]
+pub struct TrackerOptions {
+    pub poll_interval: Duration,
+    /// Blocks on top of the settlement block before an order counts as settled
+    pub confirmations: u64,
+    pub timeout: Duration,
+    /// How far behind the block at which tracking starts to look for the settlement log,
+    /// for orders that settled before they were tracked
+    pub lookback_blocks: u64,
+}
+
+impl TrackerOptions {
+    pub fn for_chain(chain: ChainId) -> Self {
+        let info = chain.info();
+        Self {
+            poll_interval: info.block_time,
+            confirmations: info.confirmations,
+            timeout: Duration::from_secs(300),
+            lookback_blocks: MAX_LOG_BLOCK_RANGE,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum SettlementStatus {
+    Settled(Box<SettledMatch>),
+    /// Not settled before the tracker's timeout
+    Expired,
+    /// The provider kept failing, or reported the order settled without a matching log
+    Failed(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TrackedOrder {
+    pub order_hash: B256,
+    pub zone: Address,
+    pub chain_id: u64,
+}
+
+#[derive(Clone)]
+pub struct SettlementTracker {
+    provider: ProviderClient,
+    nodes: HashMap<u64, ChainNode>,
+    options: TrackerOptions,
+}
+
+impl SettlementTracker {
+    pub fn new(provider: ProviderClient, options: TrackerOptions) -> Self {
+        Self { provider, nodes: HashMap::new(), options }
+    }
+
+    /// Node to read settlement logs from for orders on `chain_id`
+    pub fn with_node(mut self, chain_id: u64, node: ChainNode) -> Self {
+        self.nodes.insert(chain_id, node);
+        self
+    }
+
+    /// Resolves once `order` is settled and confirmed, or the timeout passes
+    pub async fn track(&self, order: TrackedOrder) -> SettlementStatus {
+        let deadline = Instant::now() + self.options.timeout;
+        let mut from_block = None;
+        let mut settled_at_block = None;
+
+        loop {
+            let last_error = match self.poll(order, &mut from_block, &mut settled_at_block).await {
+                Ok(Some(settled)) => return SettlementStatus::Settled(Box::new(settled)),
+                Ok(None) => None,
+                Err(e) => Some(e.to_string()),
+            };
+
+            if Instant::now() + self.options.poll_interval > deadline {
+                return match (last_error, settled_at_block) {
+                    (Some(error), _) => SettlementStatus::Failed(error),
+                    (None, Some(block)) => SettlementStatus::Failed(format!(
+                        "Order {} settled at block {} but was not confirmed in time",
+                        order.order_hash, block
+                    )),
+                    (None, None) => SettlementStatus::Expired,
+                };
+            }
+            sleep(self.options.poll_interval).await;
+        }
+    }
+
+    /// Spawns a tracking task per order
+    pub fn track_all(
+        &self,
+        orders: impl IntoIterator<Item = TrackedOrder>,
+    ) -> HashMap<B256, JoinHandle<SettlementStatus>> {
+        orders
+            .into_iter()
+            .map(|order| {
+                let tracker = self.clone();
+                (order.order_hash, tokio::spawn(async move { tracker.track(order).await }))
+            })
+            .collect()
+    }
+
+    async fn poll(
+        &self,
+        order: TrackedOrder,
+        from_block: &mut Option<u64>,
+        settled_at_block: &mut Option<u64>,
+    ) -> AoriResult<Option<SettledMatch>> {
+        let node = self.nodes.get(&order.chain_id).ok_or_else(|| {
+            AoriError::Config(format!("no node configured for chain {}", order.chain_id))
+        })?;
+        let current = self.block_number(order.chain_id).await?;
+        let from = *from_block.get_or_insert(current.saturating_sub(self.options.lookback_blocks));
+
+        if settled_at_block.is_none() {
+            let response = self
+                .provider
+                .has_order_settled(
+                    order.order_hash.to_string(),
+                    order.zone.to_string(),
+                    order.chain_id,
+                )
+                .await?;
+            if !parse_settled(&response.result)? {
+                return Ok(None);
+            }
+        }
+
+        let matches = scan_orders_settled(node, order.zone, from, current).await?;
+        let Some(settled) = matches.into_iter().find(|settled| {
+            settled.maker_order_hash == order.order_hash
+                || settled.taker_order_hash == order.order_hash
+        }) else {
+            return Err(AoriError::Provider(format!(
+                "Order {} is settled but no OrdersSettled log was found since block {}",
+                order.order_hash, from
+            )));
+        };
+
+        let block = settled.block_number.map_or(current, |block| block.saturating_to());
+        *settled_at_block = Some(block);
+        // keep rescanning from the settlement block in case of a reorg
+        *from_block = Some(block);
+
+        let confirmed = current >= block + self.options.confirmations.saturating_sub(1);
+        Ok(confirmed.then_some(settled))
+    }
+
+    async fn block_number(&self, chain_id: u64) -> AoriResult<u64> {
+        let response = self.provider.get_block_number(chain_id).await?;
+        parse_quantity(&response.result).map(|n| n.saturating_to())
+    }
+}
+
+fn parse_settled(result: &str) -> AoriResult<bool> {
+    match result.trim().trim_matches('"') {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        other => parse_quantity(other).map(|n| !n.is_zero()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::mock_server;
+    use jsonrpsee::server::{RpcModule, ServerHandle};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    fn event() -> OrdersSettled {
+        OrdersSettled {
+            makerHash: B256::repeat_byte(5),
+            takerHash: B256::repeat_byte(6),
+            maker: Address::repeat_byte(1),
+            taker: Address::repeat_byte(7),
+            inputChainId: U256::from(42161),
+            outputChainId: U256::from(42161),
+            inputZone: Address::repeat_byte(3),
+            outputZone: Address::repeat_byte(3),
+            inputToken: Address::repeat_byte(2),
+            outputToken: Address::repeat_byte(4),
+            inputAmount: U256::from(1000000000000000000_u64),
+            outputAmount: U256::from(2000000000000000000_u64),
+            matchingHash: B256::repeat_byte(8),
+        }
+    }
+
+    #[test]
+    fn decodes_orders_settled_log() {
+        let event = event();
+        let topics: Vec<B256> = event.encode_topics().into_iter().map(B256::from).collect();
+        assert_eq!(topics[0], orders_settled_topic());
+
+        let json = serde_json::json!({
+            "address": Address::repeat_byte(3),
+            "topics": topics,
+            "data": Bytes::from(event.encode_data()),
+            "transactionHash": B256::repeat_byte(9),
+            "blockNumber": "0x9fb85b",
+            "blockTimestamp": "0x6631a8f0",
+        });
+        let log: AoriLog = serde_json::from_value(json).unwrap();
+        let settled = SettledMatch::try_from(&log).unwrap();
+
+        assert_eq!(settled.maker_order_hash, event.makerHash);
+        assert_eq!(settled.taker_order_hash, event.takerHash);
+        assert_eq!(settled.output_amount, event.outputAmount);
+        assert_eq!(settled.matching_hash, event.matchingHash);
+        assert_eq!(settled.transaction_hash, Some(B256::repeat_byte(9)));
+        assert_eq!(settled.block_number, Some(U256::from(10467419)));
+        assert_eq!(settled.timestamp, Some(U256::from(0x6631a8f0_u64)));
+    }
+
+    struct MockZone {
+        block: AtomicU64,
+        settle_at: u64,
+    }
+
+    /// Block timestamps the mock node reports; its logs leave `blockTimestamp` out
+    fn block_timestamp(block: u64) -> u64 {
+        1_700_000_000 + 12 * block
+    }
+
+    /// Serves both the data provider and the chain node
+    async fn mock_provider(settle_at: u64) -> (ProviderClient, ChainNode, ServerHandle) {
+        let mut module = RpcModule::new(MockZone { block: AtomicU64::new(100), settle_at });
+        module
+            .register_method("aori_getBlockNumber", |_, zone| {
+                json!({ "id": 1, "result": zone.block.fetch_add(1, Ordering::SeqCst).to_string() })
+            })
+            .unwrap();
+        module
+            .register_method("aori_hasOrderSettled", |_, zone| {
+                let settled = zone.block.load(Ordering::SeqCst) > zone.settle_at;
+                json!({ "id": 1, "result": settled.to_string() })
+            })
+            .unwrap();
+        module
+            .register_method("eth_getLogs", |params, zone| {
+                let filter: serde_json::Value = params.sequence().next().unwrap();
+                let block = |key: &str| filter[key].as_str().unwrap().parse::<U256>().unwrap();
+                let (from, to) = (block("fromBlock").to::<u64>(), block("toBlock").to::<u64>());
+                let logs: Vec<AoriLog> = (from..=to)
+                    .filter(|block| *block == zone.settle_at)
+                    .map(|block| AoriLog {
+                        address: Address::repeat_byte(3),
+                        topics: event().encode_topics().into_iter().map(B256::from).collect(),
+                        data: event().encode_data().into(),
+                        transaction_hash: Some(B256::repeat_byte(9)),
+                        block_number: Some(U256::from(block)),
+                        block_timestamp: None,
+                    })
+                    .collect();
+                json!(logs)
+            })
+            .unwrap();
+        module
+            .register_method("eth_getBlockByNumber", |params, _| {
+                let (block, _): (U256, bool) = params.parse().unwrap();
+                json!({ "timestamp": U256::from(block_timestamp(block.to())) })
+            })
+            .unwrap();
+
+        let (url, handle) = mock_server(module).await;
+        let node = ChainNode::new(&url).unwrap();
+        (ProviderClient::new(vec![url]), node, handle)
+    }
+
+    fn options(timeout_ms: u64) -> TrackerOptions {
+        TrackerOptions {
+            poll_interval: Duration::from_millis(5),
+            confirmations: 3,
+            timeout: Duration::from_millis(timeout_ms),
+            lookback_blocks: 10,
+        }
+    }
+
+    #[tokio::test]
+    async fn tracker_waits_for_confirmations() {
+        let (provider, node, handle) = mock_provider(102).await;
+        let tracker = SettlementTracker::new(provider, options(5_000)).with_node(42161, node);
+        let order = TrackedOrder {
+            order_hash: event().takerHash,
+            zone: Address::repeat_byte(3),
+            chain_id: 42161,
+        };
+
+        let mut tracked = tracker.track_all([order]);
+        let status = tracked.remove(&order.order_hash).unwrap().await.unwrap();
+        let SettlementStatus::Settled(settled) = status else {
+            panic!("expected settlement, got {:?}", status);
+        };
+        assert_eq!(settled.block_number, Some(U256::from(102)));
+        assert_eq!(settled.transaction_hash, Some(B256::repeat_byte(9)));
+        assert_eq!(settled.timestamp, Some(U256::from(block_timestamp(102))));
+
+        handle.stop().unwrap();
+    }
+
+    #[tokio::test]
+    async fn tracker_expires_unsettled_orders() {
+        let (provider, node, handle) = mock_provider(u64::MAX).await;
+        let tracker = SettlementTracker::new(provider, options(50)).with_node(42161, node);
+        let order = TrackedOrder {
+            order_hash: B256::repeat_byte(1),
+            zone: Address::repeat_byte(3),
+            chain_id: 42161,
+        };
+
+        assert!(matches!(tracker.track(order).await, SettlementStatus::Expired));
+        assert!(parse_settled("0x1").unwrap());
+        assert!(!parse_settled("false").unwrap());
+
+        handle.stop().unwrap();
+    }
+
+    #[tokio::test]
+    async fn scan_looks_up_missing_timestamps() {
+        let (_, node, handle) = mock_provider(4_500).await;
+
+        // spans three log requests
+        let settled = scan_orders_settled(&node, Address::repeat_byte(3), 0, 5_000).await.unwrap();
+        assert_eq!(settled.len(), 1);
+        assert_eq!(settled[0].block_number, Some(U256::from(4_500)));
+        assert_eq!(settled[0].timestamp, Some(U256::from(block_timestamp(4_500))));
+
+        let tracker = SettlementTracker::new(ProviderClient::new(vec![]), options(50));
+        let order = TrackedOrder {
+            order_hash: B256::repeat_byte(1),
+            zone: Address::repeat_byte(3),
+            chain_id: 10,
+        };
+        assert!(matches!(tracker.track(order).await, SettlementStatus::Failed(_)));
+
+        handle.stop().unwrap();
+    }
+
+    #[test]
+    fn rejects_other_events() {
+        let topics: Vec<B256> = event().encode_topics().into_iter().map(B256::from).collect();
+        let data = event().encode_data();
+        assert!(decode_orders_settled(&topics[1..], &data).is_err());
+        assert!(decode_orders_settled(&topics, &data[32..]).is_err());
+    }
+}
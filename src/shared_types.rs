@@ -4,15 +4,15 @@ use alloy_serde_macro::{
     U256_from_u32,
 };
 use alloy_sol_types::{sol, SolValue};
-use bson::Bson;
 use chrono::Utc;
-use serde::{Deserialize, Deserializer, Serialize};
-use serde_json::Value;
-use tracing::error;
+use serde::{Deserialize, Serialize};
 
 use super::{
-    constants::{ChainId, SUPPORTED_AORI_CHAINS},
+    bson_codec::BsonCodec,
+    error::{AoriResult, ValidationError},
     get_order_signer,
+    rate::Rate,
+    zones::ZoneRegistry,
 };
 
 // abigen!(AoriV2, "src/aori/abi/AoriV2.json");
@@ -137,7 +137,7 @@ pub struct OrderView {
     pub output_chain_id: U256,
     pub output_zone: Address,
 
-    pub rate: String,
+    pub rate: Rate,
     #[serde(serialize_with = "U256_as_u32", deserialize_with = "U256_from_u32")]
     pub created_at: U256,
     #[serde(serialize_with = "U256_as_u32", deserialize_with = "U256_from_u32")]
@@ -146,6 +146,14 @@ pub struct OrderView {
     pub is_public: bool,
 }
 
+impl OrderView {
+    /// Recomputes `rate` in whole tokens rather than base units
+    pub fn with_decimals(mut self, input_decimals: u8, output_decimals: u8) -> Self {
+        self.rate = Rate::for_order(&self.order, input_decimals, output_decimals);
+        self
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct DetailsToExecute {
@@ -231,14 +239,6 @@ pub struct SettledMatch {
     pub timestamp: Option<U256>,
 }
 
-pub fn deserialize_rate<'de, D>(deserializer: D) -> Result<String, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let rate: f64 = Deserialize::deserialize(deserializer)?;
-    Ok(rate.to_string())
-}
-
 pub fn get_order_hash(order: AoriOrder) -> B256 {
     keccak256(order.abi_encode_packed())
 }
@@ -256,18 +256,18 @@ pub fn to_order_view(
     let order2 = order.clone();
 
     OrderView {
-        offerer: order.offerer.clone(),
-        signature: signature.clone(),
-        input_token: order.inputToken.clone(),
-        input_amount: order.inputAmount.clone(),
-        input_chain_id: order.inputChainId.clone(),
-        input_zone: order.inputZone.clone(),
-        output_token: order.outputToken.clone(),
-        output_amount: order.outputAmount.clone(),
-        output_chain_id: order.outputChainId.clone(),
-        output_zone: order.outputZone.clone(),
-
-        rate: (order.outputAmount.clone() / order.inputAmount.clone()).to_string(),
+        offerer: order.offerer,
+        signature,
+        input_token: order.inputToken,
+        input_amount: order.inputAmount,
+        input_chain_id: order.inputChainId,
+        input_zone: order.inputZone,
+        output_token: order.outputToken,
+        output_amount: order.outputAmount,
+        output_chain_id: order.outputChainId,
+        output_zone: order.outputZone,
+
+        rate: Rate::from_amounts(order.inputAmount, order.outputAmount),
         order: order2,
         order_hash: get_order_hash(order),
 
@@ -278,21 +278,24 @@ pub fn to_order_view(
     }
 }
 
+/// Builds the `DetailsToExecute` the backend sends for `matching`. The settlement happens on
+/// the maker's input chain, with the maker's input as `input_*` and the taker's input as
+/// `output_*`
 pub fn to_details_to_execute(
     matching: AoriMatchingDetails,
     matching_signature: String,
     to: Address,
     value: U256,
     data: Vec<u8>,
+    taker_permit_signature: Option<String>,
 ) -> DetailsToExecute {
-    let matching2 = matching.clone();
     let maker_order = matching.makerOrder.clone();
     let taker_order = matching.takerOrder.clone();
 
     DetailsToExecute {
         matching_hash: get_matching_hash(
-            matching.makerSignature,
-            matching.takerSignature,
+            matching.makerSignature.clone(),
+            matching.takerSignature.clone(),
             matching.blockDeadline,
             matching.seatNumber,
             matching.seatHolder,
@@ -303,20 +306,20 @@ pub fn to_details_to_execute(
         maker_zone: maker_order.inputZone,
         taker_chain_id: taker_order.inputChainId,
         taker_zone: taker_order.inputZone,
-        chain_id: matching.blockDeadline,
+        chain_id: maker_order.inputChainId,
         to,
         value,
         data,
-        taker_permit_signature: None,
-        maker: maker_order.clone().offerer,
-        taker: taker_order.clone().offerer,
-        input_token: maker_order.clone().inputToken,
-        input_amount: maker_order.clone().inputAmount,
-        output_token: taker_order.outputToken,
-        output_amount: taker_order.outputAmount,
+        taker_permit_signature,
+        maker: maker_order.offerer,
+        taker: taker_order.offerer,
+        input_token: maker_order.inputToken,
+        input_amount: maker_order.inputAmount,
+        output_token: taker_order.inputToken,
+        output_amount: taker_order.inputAmount,
         maker_order_hash: get_order_hash(maker_order),
         taker_order_hash: get_order_hash(taker_order),
-        matching: matching2,
+        matching,
     }
 }
 
@@ -349,32 +352,12 @@ pub fn calldata_to_settle_orders(matching: AoriMatchingDetails) -> Vec<u8> {
     calldata
 }
 
-pub fn document_to_order(document: bson::Document) -> anyhow::Result<OrderView> {
-    let bson: Bson = Bson::Document(document);
-    let json: Value = match bson {
-        Bson::Document(document) => {
-            serde_json::to_value(document).expect("Failed to convert Bson to Json")
-        }
-        _ => {
-            let e =
-                std::io::Error::new(std::io::ErrorKind::Other, "Failed to convert Bson to Json");
-            error!("{}", e);
-            return Err(e.into());
-        }
-    };
-
-    println!("{:?}", json);
-
-    // Deserialize the JSON value into the IntermediateRoot struct
-    let order_view: OrderView = match serde_json::from_value(json) {
-        Ok(intermediate) => intermediate,
-        Err(e) => {
-            error!("Failed to deserialize JSON: {}", e);
-            return Err(e.into());
-        }
-    };
+pub fn order_to_document(order: &OrderView) -> AoriResult<bson::Document> {
+    order.to_document()
+}
 
-    Ok(order_view)
+pub fn document_to_order(document: bson::Document) -> AoriResult<OrderView> {
+    OrderView::from_document(&document)
 }
 
 pub fn blank_out_signature(order: OrderView) -> OrderView {
@@ -428,59 +411,80 @@ pub fn blank_out_signature(order: OrderView) -> OrderView {
 //     return null;
 // }
 
-// Note: Some() is used to return an error message if the order is invalid
-pub async fn validate_order(order: AoriOrder, signature: String) -> Result<String, &'static str> {
+// Note: Ok() returns the recovered signer of the order if the order is valid
+/// Checks chains and zones against `zones`, e.g. `ZoneRegistry::builtin()` or a registry
+/// built from a `DeploymentConfig`
+pub async fn validate_order(
+    order: AoriOrder,
+    signature: String,
+    zones: &ZoneRegistry,
+) -> AoriResult<String> {
     let order2 = order.clone();
 
-    if !SUPPORTED_AORI_CHAINS().contains(&order2.inputChainId) {
-        return Err("Input chain not supported");
+    if zones.supported_chain(order2.inputChainId).is_err() {
+        return Err(ValidationError::InputChainNotSupported().into());
     }
 
-    if !SUPPORTED_AORI_CHAINS().contains(&order2.outputChainId) {
-        return Err("Output chain not supported");
+    if zones.supported_chain(order2.outputChainId).is_err() {
+        return Err(ValidationError::OutputChainNotSupported().into());
     }
 
-    if signature == "" || signature == "0x" {
-        return Err("No signature provided");
+    if signature.is_empty() || signature == "0x" {
+        return Err(ValidationError::NoSignatureProvided().into());
     }
 
     if order2.inputToken == order2.outputToken && order2.inputChainId == order2.outputChainId {
-        return Err("Input token and output token must be different if they are on the same chain");
+        return Err(ValidationError::InputAndOutputTokensAreTheSame().into());
     }
 
     if order2.inputAmount == U256::ZERO {
-        return Err("Input amount cannot be zero");
+        return Err(ValidationError::InputAmountIsZero().into());
     }
 
     if order2.outputAmount == U256::ZERO {
-        return Err("Output amount cannot be zero");
+        return Err(ValidationError::OutputAmountIsZero().into());
+    }
+
+    if zones.validate_zone(order2.inputChainId, order2.inputZone).is_err() {
+        return Err(ValidationError::ZoneNotSupported(order2.inputZone, order2.inputChainId).into());
+    }
+
+    if zones.validate_zone(order2.outputChainId, order2.outputZone).is_err() {
+        return Err(
+            ValidationError::ZoneNotSupported(order2.outputZone, order2.outputChainId).into()
+        );
     }
 
     if order2.startTime > order2.endTime {
-        return Err("Start time cannot be after end time");
+        return Err(ValidationError::StartTimeAfterEndTime().into());
     }
 
     if order2.endTime < U256::from(Utc::now().timestamp()) {
-        return Err("End time cannot be in the past");
+        return Err(ValidationError::EndTimeInThePast().into());
     }
 
     // Verify that the signature of the taker order is valid
     let order_message_signer = match get_order_signer(order, &signature).await {
         Ok(signer) => signer,
-        Err(e) => return Err("Signature signer could not be retrieved"),
+        Err(_) => return Err(ValidationError::SignerNotRecoverable().into()),
     };
 
     // TODO: add in isValidCall to validate the signature for vaults
 
-    return Ok(order_message_signer.to_string());
+    Ok(format!("{:?}", order_message_signer))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        config::{DeploymentConfig, ZoneDeployment},
+        error::AoriError,
+        sign_order,
+    };
     use alloy_primitives::{keccak256, Address, U256};
     use alloy_sol_types::SolValue;
-    use serde_json;
+    use serde_json::{self, Value};
 
     #[test]
     fn hash_order() {
@@ -537,6 +541,108 @@ mod tests {
         println!("Deserialized AoriOrder: {:?}", deserialized);
     }
 
+    #[tokio::test]
+    async fn validate_order_rejects_unsupported_chain() {
+        let order = AoriOrder {
+            inputChainId: U256::from(1),
+            outputChainId: U256::from(1),
+            ..Default::default()
+        };
+
+        let result = validate_order(order, "0x".to_string(), ZoneRegistry::builtin()).await;
+        assert!(matches!(
+            result,
+            Err(AoriError::Validation(ValidationError::InputChainNotSupported()))
+        ));
+    }
+
+    #[tokio::test]
+    async fn validate_order_accepts_zones_from_deployment_config() {
+        let fork_zone = Address::repeat_byte(0x42);
+        let mut config = DeploymentConfig::local_fork();
+        config.zones.push(ZoneDeployment {
+            chain_id: 42161,
+            zones: vec![fork_zone],
+            version: Default::default(),
+        });
+
+        let order = AoriOrder {
+            offerer: "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf".parse().unwrap(),
+            inputToken: Address::repeat_byte(0x01),
+            inputAmount: U256::from(1),
+            inputChainId: U256::from(42161),
+            inputZone: fork_zone,
+            outputToken: Address::repeat_byte(0x02),
+            outputAmount: U256::from(1),
+            outputChainId: U256::from(42161),
+            outputZone: fork_zone,
+            endTime: U256::from(Utc::now().timestamp() + 3600),
+            ..Default::default()
+        };
+        let key = "0000000000000000000000000000000000000000000000000000000000000001";
+        let signature = sign_order(order.clone(), key).await.unwrap();
+
+        let zones = config.zone_registry().unwrap();
+        let signer = validate_order(order.clone(), signature.clone(), &zones).await.unwrap();
+        assert_eq!(signer.parse::<Address>().unwrap(), order.offerer);
+
+        assert!(matches!(
+            validate_order(order, signature, ZoneRegistry::builtin()).await,
+            Err(AoriError::Validation(ValidationError::ZoneNotSupported(..)))
+        ));
+    }
+
+    const ORDER_TO_EXECUTE: &str = include_str!("../tests/fixtures/order_to_execute.json");
+
+    fn recorded_details() -> DetailsToExecute {
+        let feed: Value = serde_json::from_str(ORDER_TO_EXECUTE).unwrap();
+        serde_json::from_value(feed["result"]["data"].clone()).unwrap()
+    }
+
+    #[test]
+    fn details_to_execute_matches_recorded_backend_payload() {
+        let recorded = recorded_details();
+        let local = to_details_to_execute(
+            recorded.matching.clone(),
+            recorded.matching_signature.clone(),
+            recorded.to,
+            recorded.value,
+            recorded.data.clone(),
+            recorded.taker_permit_signature.clone(),
+        );
+
+        assert_eq!(local.chain_id, U256::from(5));
+        assert_eq!(local.maker_order_hash, recorded.maker_order_hash);
+        assert_eq!(local.taker_order_hash, recorded.taker_order_hash);
+        assert_eq!(serde_json::to_value(&local).unwrap(), serde_json::to_value(&recorded).unwrap());
+    }
+
+    #[test]
+    fn order_to_execute_round_trips_backend_json() {
+        let crate::AoriFeedEvents::OrderToExecute(data) =
+            crate::deserialize_aori_feed_event(ORDER_TO_EXECUTE).unwrap()
+        else {
+            panic!("fixture is not an OrderToExecute event");
+        };
+
+        assert_eq!(
+            data.matching_hash,
+            "0x726289b35d035541068cd3833f8dabc4a2f96580047c3a79ef0735fcba9aba64"
+                .parse::<B256>()
+                .unwrap()
+        );
+        assert_eq!(data.chain_id, U256::from(5));
+        assert_eq!(data.taker_permit_signature, None);
+
+        // serializing the typed details gives back the JSON the backend sent, up to the
+        // checksum casing of the one address it sent checksummed
+        let feed: Value = serde_json::from_str(ORDER_TO_EXECUTE).unwrap();
+        let mut sent = feed["result"]["data"].clone();
+        let seat_holder = sent["matching"]["seatHolder"].as_str().unwrap().to_lowercase();
+        sent["matching"]["seatHolder"] = seat_holder.into();
+        assert_eq!(serde_json::to_value(&*data).unwrap(), sent);
+    }
+
     // #[test]
     // fn serialize_view_orderbook_query() {
     //     // hash wallet address and sign first
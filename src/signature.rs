@@ -1,31 +1,284 @@
-use anyhow::Result;
+use alloy_primitives::B256;
 use ethers::{
-    signers::{LocalWallet, Signer}, types::{Address, RecoveryMessage, Signature, H256}, utils::{hash_message, hex}
+    signers::{LocalWallet, Signer},
+    types::{Address, RecoveryMessage, Signature, H256},
 };
 use std::str::FromStr;
 
-use super::{get_order_hash, AoriOrder};
+use super::{
+    calldata_to_settle_orders,
+    config::DeploymentConfig,
+    error::{AoriError, AoriResult, ValidationError},
+    get_matching_hash, get_order_hash, AoriOrder, DetailsToExecute,
+};
 
-pub async fn sign_order(order: AoriOrder, key: &str) -> Result<String> {
-    let wallet = LocalWallet::from_str(key).unwrap();
-    let order_hash = get_order_hash(order.clone());
-    let signature = wallet.sign_message(H256::from_slice(order_hash.as_slice())).await.unwrap();
+pub async fn sign_order(order: AoriOrder, key: &str) -> AoriResult<String> {
+    let wallet = LocalWallet::from_str(key)?;
+    sign_order_with_wallet(order, &wallet).await
+}
+
+/// Like `sign_order`, for wallets loaded some other way, e.g. from a keystore
+pub async fn sign_order_with_wallet(order: AoriOrder, wallet: &LocalWallet) -> AoriResult<String> {
+    let order_hash = get_order_hash(order);
+    let signature = wallet.sign_message(H256::from_slice(order_hash.as_slice())).await?;
     Ok(signature.to_string())
 }
 
-pub async fn get_order_signer(order: AoriOrder, signature: &str) -> Result<Address> {
+pub async fn get_order_signer(order: AoriOrder, signature: &str) -> AoriResult<Address> {
     let order_hash = get_order_hash(order.clone());
 
     let signature = Signature::from_str(signature)?;
-    println!("Signature: {:?}", signature);
 
-    let hash_bytes = hex::decode(order_hash)?;
-    let message = RecoveryMessage::Data(hash_bytes);
+    let message = RecoveryMessage::Data(order_hash.to_vec());
+
+    Ok(signature.recover(message)?)
+}
+
+/// Signs a matching hash the way the backend does for `DetailsToExecute`
+pub async fn sign_matching(matching_hash: B256, key: &str) -> AoriResult<String> {
+    let wallet = LocalWallet::from_str(key)?;
+    let signature = wallet.sign_message(H256::from_slice(matching_hash.as_slice())).await?;
+    Ok(signature.to_string())
+}
+
+pub fn get_matching_signer(matching_hash: B256, signature: &str) -> AoriResult<Address> {
+    let signature = Signature::from_str(signature)?;
+    let message = RecoveryMessage::Data(matching_hash.to_vec());
+    Ok(signature.recover(message)?)
+}
+
+/// Checks a `DetailsToExecute` from the backend before any transaction is built from it
+pub struct MatchingVerifier {
+    server_signer: alloy_primitives::Address,
+}
+
+impl MatchingVerifier {
+    pub fn new(server_signer: alloy_primitives::Address) -> Self {
+        Self { server_signer }
+    }
+
+    pub fn from_config(config: &DeploymentConfig) -> AoriResult<Self> {
+        config
+            .server_signer
+            .map(Self::new)
+            .ok_or_else(|| AoriError::Config("no server signer configured".to_string()))
+    }
+
+    pub fn verify(&self, details: &DetailsToExecute) -> AoriResult<()> {
+        let matching = &details.matching;
+        let matching_hash = get_matching_hash(
+            matching.makerSignature.clone(),
+            matching.takerSignature.clone(),
+            matching.blockDeadline,
+            matching.seatNumber,
+            matching.seatHolder,
+            matching.seatPercentOfFees,
+        );
+        if matching_hash != details.matching_hash {
+            return Err(ValidationError::MatchingHashMismatch().into());
+        }
+
+        let signer = get_matching_signer(matching_hash, &details.matching_signature)
+            .map_err(|_| ValidationError::SignerNotRecoverable())?;
+        let signer = alloy_primitives::Address::from(signer.0);
+        if signer != self.server_signer {
+            return Err(ValidationError::ServerSignerMismatch(signer).into());
+        }
+
+        if get_order_hash(matching.makerOrder.clone()) != details.maker_order_hash {
+            return Err(ValidationError::MakerOrderHashMismatch().into());
+        }
+        if get_order_hash(matching.takerOrder.clone()) != details.taker_order_hash {
+            return Err(ValidationError::TakerOrderHashMismatch().into());
+        }
 
-    println!("Message: {:?}", message);
+        // the rest of the details are copies of the signed matching, and are what a transaction
+        // is built from, so each must agree with it
+        let maker_order = &matching.makerOrder;
+        let taker_order = &matching.takerOrder;
+        let fields = [
+            ("chainId", details.chain_id == maker_order.inputChainId),
+            ("makerChainId", details.maker_chain_id == maker_order.inputChainId),
+            ("makerZone", details.maker_zone == maker_order.inputZone),
+            ("takerChainId", details.taker_chain_id == taker_order.inputChainId),
+            ("takerZone", details.taker_zone == taker_order.inputZone),
+            ("to", details.to == maker_order.inputZone),
+            ("maker", details.maker == maker_order.offerer),
+            ("taker", details.taker == taker_order.offerer),
+            ("inputToken", details.input_token == maker_order.inputToken),
+            ("inputAmount", details.input_amount == maker_order.inputAmount),
+            ("outputToken", details.output_token == taker_order.inputToken),
+            ("outputAmount", details.output_amount == taker_order.inputAmount),
+            (
+                "data",
+                details.data.is_empty() ||
+                    details.data == calldata_to_settle_orders(matching.clone()),
+            ),
+        ];
+        if let Some((field, _)) = fields.iter().find(|(_, matches)| !matches) {
+            return Err(ValidationError::DetailsFieldMismatch(field).into());
+        }
 
-    let signer_address = signature.recover(message.clone())?;
-    let signer_address = format!("0x{}", hex::encode(signer_address.as_bytes()));
+        Ok(())
+    }
 
-    Ok(Address::from_str(&signer_address)?)
-}
\ No newline at end of file
+    /// Like `verify`, also checking the matching executes the taker order we signed
+    pub fn verify_for_taker(
+        &self,
+        details: &DetailsToExecute,
+        taker_order: &AoriOrder,
+    ) -> AoriResult<()> {
+        self.verify(details)?;
+        if get_order_hash(taker_order.clone()) != details.taker_order_hash {
+            return Err(ValidationError::TakerOrderHashMismatch().into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{to_details_to_execute, AoriMatchingDetails};
+    use alloy_primitives::{Address, Bytes, U256};
+
+    const SERVER_KEY: &str = "0000000000000000000000000000000000000000000000000000000000000002";
+
+    fn order(offerer: u8, input: u8, output: u8) -> AoriOrder {
+        AoriOrder {
+            offerer: Address::repeat_byte(offerer),
+            inputToken: Address::repeat_byte(input),
+            inputAmount: U256::from(1000),
+            inputChainId: U256::from(42161),
+            outputToken: Address::repeat_byte(output),
+            outputAmount: U256::from(2000),
+            outputChainId: U256::from(42161),
+            inputZone: Address::repeat_byte(3),
+            outputZone: Address::repeat_byte(3),
+            ..Default::default()
+        }
+    }
+
+    async fn signed_details() -> DetailsToExecute {
+        let matching = AoriMatchingDetails {
+            makerOrder: order(1, 2, 4),
+            takerOrder: order(7, 4, 2),
+            makerSignature: Bytes::from(vec![1; 65]),
+            takerSignature: Bytes::from(vec![2; 65]),
+            blockDeadline: U256::from(10467500),
+            seatNumber: U256::ZERO,
+            seatHolder: Address::repeat_byte(9),
+            seatPercentOfFees: U256::ZERO,
+        };
+        let zone = matching.makerOrder.inputZone;
+        let mut details =
+            to_details_to_execute(matching, String::new(), zone, U256::ZERO, vec![], None);
+        details.matching_signature =
+            sign_matching(details.matching_hash, SERVER_KEY).await.unwrap();
+        details
+    }
+
+    fn server_signer() -> Address {
+        let wallet = LocalWallet::from_str(SERVER_KEY).unwrap();
+        Address::from(wallet.address().0)
+    }
+
+    #[tokio::test]
+    async fn recovers_order_signer() {
+        let order = order(1, 2, 4);
+        let signature = sign_order(order.clone(), SERVER_KEY).await.unwrap();
+        let signer = get_order_signer(order, &signature).await.unwrap();
+        assert_eq!(Address::from(signer.0), server_signer());
+    }
+
+    #[tokio::test]
+    async fn verifies_server_signed_matchings() {
+        let details = signed_details().await;
+        let verifier = MatchingVerifier::new(server_signer());
+        verifier.verify(&details).unwrap();
+        verifier.verify_for_taker(&details, &order(7, 4, 2)).unwrap();
+
+        let impostor = MatchingVerifier::new(Address::repeat_byte(1));
+        assert!(matches!(
+            impostor.verify(&details),
+            Err(AoriError::Validation(ValidationError::ServerSignerMismatch(_)))
+        ));
+        assert!(matches!(
+            verifier.verify_for_taker(&details, &order(8, 4, 2)),
+            Err(AoriError::Validation(ValidationError::TakerOrderHashMismatch()))
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_tampered_matchings() {
+        let verifier = MatchingVerifier::new(server_signer());
+
+        let mut details = signed_details().await;
+        details.matching.seatPercentOfFees = U256::from(100);
+        assert!(matches!(
+            verifier.verify(&details),
+            Err(AoriError::Validation(ValidationError::MatchingHashMismatch()))
+        ));
+
+        let mut details = signed_details().await;
+        details.matching.makerOrder.outputAmount = U256::from(1);
+        assert!(matches!(
+            verifier.verify(&details),
+            Err(AoriError::Validation(ValidationError::MakerOrderHashMismatch()))
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_details_that_disagree_with_the_matching() {
+        let verifier = MatchingVerifier::new(server_signer());
+        type Tamper = fn(&mut DetailsToExecute);
+        let tampered: [(&str, Tamper); 13] = [
+            ("chainId", |d| d.chain_id = U256::from(1)),
+            ("makerChainId", |d| d.maker_chain_id = U256::from(1)),
+            ("makerZone", |d| d.maker_zone = Address::repeat_byte(8)),
+            ("takerChainId", |d| d.taker_chain_id = U256::from(1)),
+            ("takerZone", |d| d.taker_zone = Address::repeat_byte(8)),
+            ("to", |d| d.to = Address::repeat_byte(8)),
+            ("maker", |d| d.maker = Address::repeat_byte(8)),
+            ("taker", |d| d.taker = Address::repeat_byte(8)),
+            ("inputToken", |d| d.input_token = Address::repeat_byte(8)),
+            ("inputAmount", |d| d.input_amount = U256::from(1)),
+            ("outputToken", |d| d.output_token = Address::repeat_byte(8)),
+            ("outputAmount", |d| d.output_amount = U256::from(1)),
+            ("data", |d| d.data = vec![0xde, 0xad]),
+        ];
+
+        for (field, tamper) in tampered {
+            let mut details = signed_details().await;
+            tamper(&mut details);
+            match verifier.verify(&details) {
+                Err(AoriError::Validation(ValidationError::DetailsFieldMismatch(f))) => {
+                    assert_eq!(f, field)
+                }
+                result => panic!("tampered {} gave {:?}", field, result),
+            }
+        }
+
+        let mut details = signed_details().await;
+        details.data = calldata_to_settle_orders(details.matching.clone());
+        verifier.verify(&details).unwrap();
+    }
+
+    #[test]
+    fn verifies_recorded_backend_matching() {
+        let feed: serde_json::Value =
+            serde_json::from_str(include_str!("../tests/fixtures/order_to_execute.json")).unwrap();
+        let details: DetailsToExecute =
+            serde_json::from_value(feed["result"]["data"].clone()).unwrap();
+
+        // the backend key that produced the recorded matchingSignature
+        let backend_signer = "0xb1a2f2a4c79c7c7ba1ac161ad0bdecf11350daa7".parse().unwrap();
+        let verifier = MatchingVerifier::new(backend_signer);
+        verifier.verify(&details).unwrap();
+
+        assert!(matches!(
+            MatchingVerifier::new(server_signer()).verify(&details),
+            Err(AoriError::Validation(ValidationError::ServerSignerMismatch(_)))
+        ));
+    }
+}
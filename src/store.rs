@@ -0,0 +1,285 @@
+// order storage
+// persists `OrderView`s in MongoDB, or in memory for tests
+
+use std::collections::HashMap;
+
+use alloy_primitives::{Address, B256, U256};
+use async_trait::async_trait;
+use bson::{doc, Bson, Document};
+use chrono::Utc;
+use futures::TryStreamExt;
+use mongodb::{
+    options::{FindOptions, ReplaceOptions},
+    Client, Collection,
+};
+use tokio::sync::RwLock;
+
+use super::{
+    bson_codec::{encode_address, encode_hash, BsonCodec},
+    error::AoriResult,
+    OrderView,
+};
+
+/// Orders matching every field that is set. Results are sorted oldest first
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OrderQuery {
+    pub offerer: Option<Address>,
+    pub input_token: Option<Address>,
+    pub output_token: Option<Address>,
+    /// Matched against the order's input chain, where it settles
+    pub chain_id: Option<u64>,
+    pub is_active: Option<bool>,
+    pub limit: Option<usize>,
+}
+
+impl OrderQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn offerer(mut self, offerer: Address) -> Self {
+        self.offerer = Some(offerer);
+        self
+    }
+
+    /// Orders selling `input_token` for `output_token`
+    pub fn pair(mut self, input_token: Address, output_token: Address) -> Self {
+        self.input_token = Some(input_token);
+        self.output_token = Some(output_token);
+        self
+    }
+
+    pub fn chain_id(mut self, chain_id: u64) -> Self {
+        self.chain_id = Some(chain_id);
+        self
+    }
+
+    pub fn active(mut self, is_active: bool) -> Self {
+        self.is_active = Some(is_active);
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn matches(&self, order: &OrderView) -> bool {
+        self.offerer.is_none_or(|offerer| order.offerer == offerer) &&
+            self.input_token.is_none_or(|token| order.input_token == token) &&
+            self.output_token.is_none_or(|token| order.output_token == token) &&
+            self.chain_id.is_none_or(|chain_id| order.input_chain_id == U256::from(chain_id)) &&
+            self.is_active.is_none_or(|is_active| order.is_active == is_active)
+    }
+}
+
+#[async_trait]
+pub trait OrderStore: Send + Sync {
+    /// Stores `order`, replacing any order with the same hash
+    async fn insert(&self, order: OrderView) -> AoriResult<()>;
+
+    /// Marks the order active or inactive, returning whether it was found
+    async fn update_status(&self, order_hash: B256, is_active: bool) -> AoriResult<bool>;
+
+    async fn get(&self, order_hash: B256) -> AoriResult<Option<OrderView>>;
+
+    async fn query(&self, query: &OrderQuery) -> AoriResult<Vec<OrderView>>;
+}
+
+#[derive(Default)]
+pub struct InMemoryOrderStore {
+    orders: RwLock<HashMap<B256, OrderView>>,
+}
+
+impl InMemoryOrderStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl OrderStore for InMemoryOrderStore {
+    async fn insert(&self, order: OrderView) -> AoriResult<()> {
+        self.orders.write().await.insert(order.order_hash, order);
+        Ok(())
+    }
+
+    async fn update_status(&self, order_hash: B256, is_active: bool) -> AoriResult<bool> {
+        let mut orders = self.orders.write().await;
+        let Some(order) = orders.get_mut(&order_hash) else {
+            return Ok(false);
+        };
+        order.is_active = is_active;
+        order.last_updated_at = U256::from(Utc::now().timestamp());
+        Ok(true)
+    }
+
+    async fn get(&self, order_hash: B256) -> AoriResult<Option<OrderView>> {
+        Ok(self.orders.read().await.get(&order_hash).cloned())
+    }
+
+    async fn query(&self, query: &OrderQuery) -> AoriResult<Vec<OrderView>> {
+        let mut orders: Vec<OrderView> = self
+            .orders
+            .read()
+            .await
+            .values()
+            .filter(|order| query.matches(order))
+            .cloned()
+            .collect();
+        orders.sort_by_key(|order| order.created_at);
+        orders.truncate(query.limit.unwrap_or(usize::MAX));
+        Ok(orders)
+    }
+}
+
+/// Stores one document per order, keyed by order hash
+pub struct MongoOrderStore {
+    collection: Collection<Document>,
+}
+
+impl MongoOrderStore {
+    pub fn new(collection: Collection<Document>) -> Self {
+        Self { collection }
+    }
+
+    pub async fn connect(uri: &str, database: &str, collection: &str) -> AoriResult<Self> {
+        let client = Client::with_uri_str(uri).await?;
+        Ok(Self::new(client.database(database).collection(collection)))
+    }
+
+    pub fn collection(&self) -> &Collection<Document> {
+        &self.collection
+    }
+
+    /// Creates the indexes `OrderQuery` filters on. Safe to call on every startup
+    pub async fn create_indexes(&self) -> AoriResult<()> {
+        self.collection.create_indexes(OrderView::indexes(), None).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl OrderStore for MongoOrderStore {
+    async fn insert(&self, order: OrderView) -> AoriResult<()> {
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.collection
+            .replace_one(hash_filter(order.order_hash), order.to_document()?, options)
+            .await?;
+        Ok(())
+    }
+
+    async fn update_status(&self, order_hash: B256, is_active: bool) -> AoriResult<bool> {
+        let update = doc! {
+            "$set": { "isActive": is_active, "lastUpdatedAt": Utc::now().timestamp() }
+        };
+        let result = self.collection.update_one(hash_filter(order_hash), update, None).await?;
+        Ok(result.matched_count > 0)
+    }
+
+    async fn get(&self, order_hash: B256) -> AoriResult<Option<OrderView>> {
+        self.collection
+            .find_one(hash_filter(order_hash), None)
+            .await?
+            .map(|document| OrderView::from_document(&document))
+            .transpose()
+    }
+
+    async fn query(&self, query: &OrderQuery) -> AoriResult<Vec<OrderView>> {
+        let options = FindOptions::builder()
+            .sort(doc! { "createdAt": 1 })
+            .limit(query.limit.map(|limit| limit as i64))
+            .build();
+        let cursor = self.collection.find(query_filter(query), options).await?;
+        let documents: Vec<Document> = cursor.try_collect().await?;
+        documents.into_iter().map(|document| OrderView::from_document(&document)).collect()
+    }
+}
+
+fn hash_filter(order_hash: B256) -> Document {
+    doc! { "_id": encode_hash(order_hash) }
+}
+
+/// Filter over the fields `OrderView::to_document` writes, encoded the same way
+fn query_filter(query: &OrderQuery) -> Document {
+    let mut filter = Document::new();
+    if let Some(offerer) = query.offerer {
+        filter.insert("offerer", encode_address(offerer));
+    }
+    if let Some(token) = query.input_token {
+        filter.insert("inputToken", encode_address(token));
+    }
+    if let Some(token) = query.output_token {
+        filter.insert("outputToken", encode_address(token));
+    }
+    if let Some(chain_id) = query.chain_id {
+        filter.insert("inputChainId", Bson::Int64(chain_id as i64));
+    }
+    if let Some(is_active) = query.is_active {
+        filter.insert("isActive", is_active);
+    }
+    filter
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{to_order_view, AoriOrder};
+
+    fn order(offerer: u8, input: u8, output: u8, chain_id: u64) -> OrderView {
+        let order = AoriOrder {
+            offerer: Address::repeat_byte(offerer),
+            inputToken: Address::repeat_byte(input),
+            inputAmount: U256::from(1000),
+            inputChainId: U256::from(chain_id),
+            outputToken: Address::repeat_byte(output),
+            outputAmount: U256::from(2000),
+            outputChainId: U256::from(chain_id),
+            ..Default::default()
+        };
+        to_order_view(order, String::new(), true, true)
+    }
+
+    #[tokio::test]
+    async fn in_memory_store_queries_and_updates() {
+        let store = InMemoryOrderStore::new();
+        let first = order(1, 2, 3, 42161);
+        let second = order(1, 3, 2, 42161);
+        let third = order(4, 2, 3, 1);
+        for order in [&first, &second, &third] {
+            store.insert(order.clone()).await.unwrap();
+        }
+
+        let by_offerer =
+            store.query(&OrderQuery::new().offerer(Address::repeat_byte(1))).await.unwrap();
+        assert_eq!(by_offerer.len(), 2);
+
+        let pair = OrderQuery::new().pair(Address::repeat_byte(2), Address::repeat_byte(3));
+        assert_eq!(store.query(&pair).await.unwrap().len(), 2);
+        let on_arbitrum = store.query(&pair.clone().chain_id(42161)).await.unwrap();
+        assert_eq!(on_arbitrum.len(), 1);
+        assert_eq!(on_arbitrum[0].order_hash, first.order_hash);
+
+        assert!(store.update_status(first.order_hash, false).await.unwrap());
+        assert!(!store.update_status(B256::ZERO, false).await.unwrap());
+        assert!(!store.get(first.order_hash).await.unwrap().unwrap().is_active);
+        assert_eq!(store.query(&pair.active(true)).await.unwrap().len(), 1);
+        assert!(store.get(B256::ZERO).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn mongo_filter_matches_stored_fields() {
+        let order = order(1, 2, 3, 42161);
+        let document = order.to_document().unwrap();
+        let filter = query_filter(
+            &OrderQuery::new()
+                .offerer(order.offerer)
+                .pair(order.input_token, order.output_token)
+                .chain_id(42161)
+                .active(true),
+        );
+        for (key, value) in filter.iter() {
+            assert_eq!(document.get(key), Some(value), "{}", key);
+        }
+    }
+}
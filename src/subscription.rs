@@ -1,8 +1,15 @@
-use std::env;
+use std::{collections::HashMap, env, fmt};
 
 use reqwest::Client;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use super::{
+    error::{AoriBackendErrors, AoriError, AoriResult},
+    AoriBroadcastParams, AoriBroadcastRequest, AoriErrorData, AoriFeedEvents,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum SubscriptionEvents {
     OrderCreated,
     OrderCancelled,
@@ -10,40 +17,273 @@ pub enum SubscriptionEvents {
     OrderFulfilled,
     OrderToExecute,
     QuoteRequested,
+    SwapRequested,
 }
 
-impl ToString for SubscriptionEvents {
-    fn to_string(&self) -> String {
-        match self {
+impl From<&AoriFeedEvents> for SubscriptionEvents {
+    fn from(event: &AoriFeedEvents) -> Self {
+        match event {
+            AoriFeedEvents::OrderCreated(_) => SubscriptionEvents::OrderCreated,
+            AoriFeedEvents::OrderCancelled(_) => SubscriptionEvents::OrderCancelled,
+            AoriFeedEvents::OrderTaken(_) => SubscriptionEvents::OrderTaken,
+            AoriFeedEvents::OrderFulfilled(_) => SubscriptionEvents::OrderFulfilled,
+            AoriFeedEvents::OrderToExecute(_) => SubscriptionEvents::OrderToExecute,
+            AoriFeedEvents::QuoteRequested(_) => SubscriptionEvents::QuoteRequested,
+            AoriFeedEvents::SwapRequested(_) => SubscriptionEvents::SwapRequested,
+        }
+    }
+}
+
+impl fmt::Display for SubscriptionEvents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
             SubscriptionEvents::OrderCreated => "OrderCreated",
             SubscriptionEvents::OrderCancelled => "OrderCancelled",
             SubscriptionEvents::OrderTaken => "OrderTaken",
             SubscriptionEvents::OrderFulfilled => "OrderFulfilled",
             SubscriptionEvents::OrderToExecute => "OrderToExecute",
             SubscriptionEvents::QuoteRequested => "QuoteRequested",
-        }
-        .to_string()
+            SubscriptionEvents::SwapRequested => "SwapRequested",
+        })
     }
 }
 
-pub async fn broadcast_subscription_event(event: SubscriptionEvents, data: serde_json::Value) {
-    let data = serde_json::json!({
-        "id": 1,
-        "jsonrpc": "2.0",
-        "method": "aori_broadcast",
-        "params": vec![serde_json::json!({
-            "secret": "",
-            "data": serde_json::json!({
-                "type": event.to_string(),
-                "data": data
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BroadcastResponse {
+    Success { id: i64, result: Value },
+    Failure { id: Option<i64>, error: AoriErrorData },
+}
+
+/// Posts `aori_broadcast` calls to a relay, reusing a single connection pool
+#[derive(Clone)]
+pub struct Broadcaster {
+    url: String,
+    secret: String,
+    client: Client,
+}
+
+impl Broadcaster {
+    pub fn new(url: impl Into<String>, secret: impl Into<String>) -> Self {
+        Self::with_client(Client::new(), url, secret)
+    }
+
+    /// Uses a preconfigured `reqwest::Client` (timeouts, proxies, pool size)
+    pub fn with_client(client: Client, url: impl Into<String>, secret: impl Into<String>) -> Self {
+        Self { url: url.into(), secret: secret.into(), client }
+    }
+
+    /// Reads `AORI_BROADCAST_URL` and `AORI_BROADCAST_SECRET`, both required
+    pub fn from_env() -> AoriResult<Self> {
+        let url = env::var("AORI_BROADCAST_URL")
+            .map_err(|_| AoriError::Config("missing AORI_BROADCAST_URL".to_string()))?;
+        let secret = env::var("AORI_BROADCAST_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty())
+            .ok_or_else(|| AoriError::Config("missing AORI_BROADCAST_SECRET".to_string()))?;
+        Ok(Self::new(url, secret))
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Broadcasts a single event, returning the relay's `result` on delivery
+    pub async fn broadcast(&self, event: &AoriFeedEvents) -> AoriResult<Value> {
+        let request = self.request(1, event)?;
+        match self.post(&request).await? {
+            BroadcastResponse::Success { result, .. } => Ok(result),
+            BroadcastResponse::Failure { error, .. } => Err(relay_error(error)),
+        }
+    }
+
+    /// Broadcasts all events as one JSON-RPC batch. The outer error is set if the batch
+    /// could not be delivered at all, otherwise each event gets its own delivery result in
+    /// the order it was given
+    pub async fn broadcast_batch(
+        &self,
+        events: &[AoriFeedEvents],
+    ) -> AoriResult<Vec<AoriResult<Value>>> {
+        if events.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let requests = events
+            .iter()
+            .enumerate()
+            .map(|(i, event)| self.request(i as i64 + 1, event))
+            .collect::<AoriResult<Vec<_>>>()?;
+        let responses: Vec<BroadcastResponse> = self.post(&requests).await?;
+
+        let mut by_id: HashMap<i64, AoriResult<Value>> = HashMap::new();
+        for response in responses {
+            match response {
+                BroadcastResponse::Success { id, result } => {
+                    by_id.insert(id, Ok(result));
+                }
+                BroadcastResponse::Failure { id: Some(id), error } => {
+                    by_id.insert(id, Err(relay_error(error)));
+                }
+                BroadcastResponse::Failure { id: None, error } => return Err(relay_error(error)),
+            }
+        }
+
+        Ok(requests
+            .iter()
+            .map(|request| {
+                by_id.remove(&request.id).unwrap_or_else(|| {
+                    Err(AoriError::Transport(format!(
+                        "No response from relay for request {}",
+                        request.id
+                    )))
+                })
             })
-        })]
-    });
-
-    let client = Client::new();
-    let _ = client
-        .post(env::var("AORI_BROADCAST_URL").expect("missing AORI_BROADCAST_URL"))
-        .json::<Value>(&data)
-        .send()
-        .await;
+            .collect())
+    }
+
+    fn request(&self, id: i64, event: &AoriFeedEvents) -> AoriResult<AoriBroadcastRequest> {
+        // `AoriFeedEvents` is adjacently tagged, so this is already `{ "type", "data" }`
+        let data = serde_json::to_value(event)?;
+        Ok(AoriBroadcastRequest {
+            id,
+            jsonrpc: "2.0".to_string(),
+            method: "aori_broadcast".to_string(),
+            params: vec![AoriBroadcastParams { secret: self.secret.clone(), data }],
+        })
+    }
+
+    async fn post<T, R>(&self, body: &T) -> AoriResult<R>
+    where
+        T: serde::Serialize + ?Sized,
+        R: serde::de::DeserializeOwned,
+    {
+        let response = self
+            .client
+            .post(&self.url)
+            .json(body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| AoriError::Transport(e.to_string()))?;
+
+        response.json::<R>().await.map_err(|e| AoriError::Encoding(e.to_string()))
+    }
+}
+
+fn relay_error(error: AoriErrorData) -> AoriError {
+    AoriError::Backend(AoriBackendErrors::from_error_message(&error.message))
+}
+
+/// One-off broadcast configured from the environment. Prefer holding a `Broadcaster` when
+/// sending more than a handful of events
+pub async fn broadcast_subscription_event(event: &AoriFeedEvents) -> AoriResult<Value> {
+    Broadcaster::from_env()?.broadcast(event).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        deserialize_aori_feed_event, to_order_view, AoriOrder, QuoteRequestedData, SettledMatch,
+    };
+    use alloy_primitives::{Address, B256, U256};
+
+    fn order() -> AoriOrder {
+        AoriOrder {
+            offerer: Address::repeat_byte(1),
+            inputToken: Address::repeat_byte(2),
+            inputAmount: U256::from(1000000000000000000_u64),
+            inputChainId: U256::from(42161),
+            inputZone: Address::repeat_byte(3),
+            outputToken: Address::repeat_byte(4),
+            outputAmount: U256::from(2000000000000000000_u64),
+            outputChainId: U256::from(42161),
+            outputZone: Address::repeat_byte(3),
+            startTime: U256::from(1619827200),
+            endTime: U256::from(1622428800),
+            salt: U256::from(1),
+            counter: U256::from(1),
+            toWithdraw: false,
+        }
+    }
+
+    /// Broadcasts `event` through the request builder and reads it back the way a feed
+    /// subscriber would
+    fn round_trip(event: AoriFeedEvents) {
+        let broadcaster = Broadcaster::new("http://localhost:8080", "hunter2");
+        let request = broadcaster.request(7, &event).unwrap();
+        assert_eq!(request.params[0].secret, "hunter2");
+        assert_eq!(
+            request.params[0].data["type"],
+            SubscriptionEvents::from(&event).to_string().as_str()
+        );
+
+        let feed = serde_json::json!({ "id": null, "result": request.params[0].data });
+        let received = deserialize_aori_feed_event(&feed.to_string()).unwrap();
+
+        assert_eq!(SubscriptionEvents::from(&received), SubscriptionEvents::from(&event));
+        assert_eq!(serde_json::to_value(&received).unwrap(), serde_json::to_value(&event).unwrap());
+    }
+
+    #[test]
+    fn order_events_round_trip() {
+        let view = to_order_view(order(), "0x1234".to_string(), true, true);
+        round_trip(AoriFeedEvents::OrderCreated(Box::new(view.clone())));
+        round_trip(AoriFeedEvents::OrderCancelled(Box::new(view.clone())));
+        round_trip(AoriFeedEvents::OrderTaken(Box::new(view.clone())));
+        round_trip(AoriFeedEvents::SwapRequested(Box::new(view)));
+    }
+
+    #[test]
+    fn quote_requested_round_trips() {
+        round_trip(AoriFeedEvents::QuoteRequested(Box::new(QuoteRequestedData {
+            input_token: Address::repeat_byte(2).to_string(),
+            output_token: Address::repeat_byte(4).to_string(),
+            input_amount: "1000000000000000000".to_string(),
+            chain_id: 42161,
+        })));
+    }
+
+    #[test]
+    fn order_fulfilled_round_trips() {
+        round_trip(AoriFeedEvents::OrderFulfilled(Box::new(SettledMatch {
+            maker_order_hash: B256::repeat_byte(5),
+            taker_order_hash: B256::repeat_byte(6),
+            maker: Address::repeat_byte(1),
+            taker: Address::repeat_byte(7),
+            input_chain_id: U256::from(42161),
+            output_chain_id: U256::from(42161),
+            input_zone: Address::repeat_byte(3),
+            output_zone: Address::repeat_byte(3),
+            input_token: Address::repeat_byte(2),
+            output_token: Address::repeat_byte(4),
+            input_amount: U256::from(1000000000000000000_u64),
+            output_amount: U256::from(2000000000000000000_u64),
+            matching_hash: B256::repeat_byte(8),
+            transaction_hash: Some(B256::repeat_byte(9)),
+            block_number: Some(U256::from(10467419)),
+            timestamp: None,
+        })));
+    }
+
+    #[test]
+    fn order_to_execute_round_trips() {
+        let feed = include_str!("../tests/fixtures/order_to_execute.json");
+        let event = deserialize_aori_feed_event(feed).unwrap();
+        round_trip(event);
+    }
+
+    #[test]
+    fn relay_responses_parse_as_success_or_failure() {
+        let responses: Vec<BroadcastResponse> = serde_json::from_str(
+            r#"[
+                {"jsonrpc":"2.0","id":2,"error":{"code":-32000,"message":"Invalid Authorization"}},
+                {"jsonrpc":"2.0","id":1,"result":null}
+            ]"#,
+        )
+        .unwrap();
+
+        assert!(matches!(responses[0], BroadcastResponse::Failure { id: Some(2), .. }));
+        assert!(matches!(responses[1], BroadcastResponse::Success { id: 1, .. }));
+    }
 }
@@ -0,0 +1,13 @@
+// test utilities
+// local JSON-RPC servers standing in for the backend, the data provider and chain nodes
+
+use jsonrpsee::server::{RpcModule, Server, ServerHandle};
+
+/// Serves `module` over HTTP on a free local port, returning its url
+pub(crate) async fn mock_server<T: Send + Sync + 'static>(
+    module: RpcModule<T>,
+) -> (String, ServerHandle) {
+    let server = Server::builder().build("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", server.local_addr().unwrap());
+    (url, server.start(module))
+}
@@ -0,0 +1,191 @@
+// zone registry
+// typed lookups for the zones orders may settle through, keyed by chain
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::OnceLock,
+};
+
+use alloy_primitives::{Address, U256};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    chains::ChainId,
+    config::DeploymentConfig,
+    constants::DEFAULT_ZONE,
+    error::{AoriResult, ValidationError},
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ZoneVersion {
+    /// Settles orders whose input and output are on the same chain
+    #[default]
+    V2SingleChain,
+    /// Reserved for zones settling across chains; none are deployed yet
+    V2CrossChain,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Zone {
+    pub address: Address,
+    pub version: ZoneVersion,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ZoneRegistry {
+    zones: HashMap<ChainId, Vec<Zone>>,
+    default_zone: Address,
+}
+
+impl ZoneRegistry {
+    /// Registry of the zones compiled into the SDK, built on first use. `DEFAULT_ZONE` isn't
+    /// listed as deployed on any chain, so `default_zone` falls back to each chain's first zone
+    /// unless a `DeploymentConfig` registers it
+    pub fn builtin() -> &'static ZoneRegistry {
+        static REGISTRY: OnceLock<ZoneRegistry> = OnceLock::new();
+        REGISTRY.get_or_init(|| {
+            let mut registry = ZoneRegistry::new(DEFAULT_ZONE.parse().unwrap_or_default());
+            for chain in ChainId::supported() {
+                for zone in chain.info().zones {
+                    registry.insert(chain, *zone, ZoneVersion::V2SingleChain);
+                }
+            }
+            registry
+        })
+    }
+
+    pub fn new(default_zone: Address) -> Self {
+        Self { zones: HashMap::new(), default_zone }
+    }
+
+    /// Builds a registry from a deployment config, failing on chains the SDK doesn't know
+    pub fn from_config(config: &DeploymentConfig) -> AoriResult<Self> {
+        let mut registry = ZoneRegistry::new(config.default_zone);
+        for deployment in &config.zones {
+            let chain = ChainId::try_from(deployment.chain_id)?;
+            for zone in &deployment.zones {
+                registry.insert(chain, *zone, deployment.version);
+            }
+        }
+        Ok(registry)
+    }
+
+    pub fn insert(&mut self, chain: ChainId, address: Address, version: ZoneVersion) {
+        let zones = self.zones.entry(chain).or_default();
+        if !zones.iter().any(|zone| zone.address == address) {
+            zones.push(Zone { address, version });
+        }
+    }
+
+    pub fn zones(&self, chain: ChainId) -> &[Zone] {
+        self.zones.get(&chain).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn zones_with_version(
+        &self,
+        chain: ChainId,
+        version: ZoneVersion,
+    ) -> impl Iterator<Item = Address> + '_ {
+        self.zones(chain)
+            .iter()
+            .filter(move |zone| zone.version == version)
+            .map(|zone| zone.address)
+    }
+
+    pub fn chains(&self) -> impl Iterator<Item = ChainId> + '_ {
+        self.zones.iter().filter(|(_, zones)| !zones.is_empty()).map(|(chain, _)| *chain)
+    }
+
+    pub fn is_chain_supported(&self, chain: ChainId) -> bool {
+        !self.zones(chain).is_empty()
+    }
+
+    pub fn is_zone_supported(&self, chain: ChainId, zone: Address) -> bool {
+        self.zones(chain).iter().any(|z| z.address == zone)
+    }
+
+    /// The configured default zone if it is deployed on `chain`, otherwise the first
+    /// single-chain zone registered for it
+    pub fn default_zone(&self, chain: ChainId) -> Option<Address> {
+        if self.is_zone_supported(chain, self.default_zone) {
+            return Some(self.default_zone);
+        }
+        self.zones_with_version(chain, ZoneVersion::V2SingleChain).next()
+    }
+
+    /// Parses a chain id from an order, failing if the chain is unknown or has no zones here
+    pub fn supported_chain(&self, chain_id: U256) -> Result<ChainId, ValidationError> {
+        let chain = ChainId::try_from(chain_id)?;
+        if !self.is_chain_supported(chain) {
+            return Err(ValidationError::UnsupportedChainId(chain_id));
+        }
+        Ok(chain)
+    }
+
+    /// Checks that `zone` is deployed on `chain_id`, as found on an order
+    pub fn validate_zone(&self, chain_id: U256, zone: Address) -> Result<ChainId, ValidationError> {
+        let chain = ChainId::try_from(chain_id)?;
+        if !self.is_zone_supported(chain, zone) {
+            return Err(ValidationError::ZoneNotSupported(zone, chain_id));
+        }
+        Ok(chain)
+    }
+
+    pub fn to_address_sets(&self) -> HashMap<ChainId, HashSet<Address>> {
+        self.zones
+            .iter()
+            .map(|(chain, zones)| (*chain, zones.iter().map(|zone| zone.address).collect()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ZoneDeployment;
+
+    #[test]
+    fn builtin_registry_matches_chain_info() {
+        let registry = ZoneRegistry::builtin();
+        for chain in ChainId::ALL {
+            for zone in chain.info().zones {
+                assert!(registry.is_zone_supported(chain, *zone));
+            }
+            assert_eq!(registry.default_zone(chain), chain.info().zones.first().copied());
+            assert_eq!(registry.is_chain_supported(chain), chain.is_supported());
+        }
+
+        let zone = ChainId::Arbitrum.info().zones[0];
+        assert!(!registry.is_zone_supported(ChainId::Ethereum, zone));
+        assert!(registry.validate_zone(U256::from(42161), Address::ZERO).is_err());
+        assert!(registry.supported_chain(U256::from(1)).is_err());
+    }
+
+    #[test]
+    fn registry_from_config_keeps_versions() {
+        let cross_chain = Address::repeat_byte(9);
+        let mut config = DeploymentConfig::testnet();
+        config.default_zone = cross_chain;
+        config.zones.push(ZoneDeployment {
+            chain_id: ChainId::Sepolia.id(),
+            zones: vec![cross_chain],
+            version: ZoneVersion::V2CrossChain,
+        });
+
+        let registry = ZoneRegistry::from_config(&config).unwrap();
+        assert!(registry.is_zone_supported(ChainId::Sepolia, cross_chain));
+        assert_eq!(registry.default_zone(ChainId::Sepolia), Some(cross_chain));
+        assert_eq!(
+            registry.default_zone(ChainId::BerachainArtio),
+            Some(ChainId::BerachainArtio.info().zones[0])
+        );
+        assert_eq!(
+            registry.zones_with_version(ChainId::Sepolia, ZoneVersion::V2CrossChain).count(),
+            1
+        );
+
+        config.zones[0].chain_id = 5;
+        assert!(ZoneRegistry::from_config(&config).is_err());
+    }
+}